@@ -0,0 +1,248 @@
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use imgui::{ImGuiCond, Ui};
+use std::cmp::max;
+use std::io::{self, BufReader, ErrorKind, Read};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use {Block, LoadReport, ProgressReader, Waker};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SampleType {
+    U8,
+    I16,
+    U16,
+    I32,
+    F32,
+    F64,
+    I8,
+    U32,
+    I64,
+    U64,
+}
+
+impl SampleType {
+    pub fn size(self) -> usize {
+        match self {
+            SampleType::U8 | SampleType::I8 => 1,
+            SampleType::I16 | SampleType::U16 => 2,
+            SampleType::I32 | SampleType::U32 | SampleType::F32 => 4,
+            SampleType::I64 | SampleType::U64 | SampleType::F64 => 8,
+        }
+    }
+
+    pub fn read(self, bytes: &[u8], big_endian: bool) -> f64 {
+        if big_endian {
+            self.decode::<BigEndian>(bytes)
+        } else {
+            self.decode::<LittleEndian>(bytes)
+        }
+    }
+
+    fn decode<B: ByteOrder>(self, bytes: &[u8]) -> f64 {
+        match self {
+            SampleType::U8 => bytes[0] as f64,
+            SampleType::I16 => B::read_i16(bytes) as f64,
+            SampleType::U16 => B::read_u16(bytes) as f64,
+            SampleType::I32 => B::read_i32(bytes) as f64,
+            SampleType::F32 => B::read_f32(bytes) as f64,
+            SampleType::F64 => B::read_f64(bytes),
+            SampleType::I8 => bytes[0] as i8 as f64,
+            SampleType::U32 => B::read_u32(bytes) as f64,
+            SampleType::I64 => B::read_i64(bytes) as f64,
+            SampleType::U64 => B::read_u64(bytes) as f64,
+        }
+    }
+}
+
+const SAMPLE_TYPES: [SampleType; 10] = [
+    SampleType::U8,
+    SampleType::I16,
+    SampleType::U16,
+    SampleType::I32,
+    SampleType::F32,
+    SampleType::F64,
+    SampleType::I8,
+    SampleType::U32,
+    SampleType::I64,
+    SampleType::U64,
+];
+
+/// How samples are laid out in a raw binary file. Every frame holds one sample
+/// of each channel, frames start `stride` bytes apart.
+#[derive(Debug, Clone)]
+pub struct Format {
+    pub header: usize,
+    pub sample_type: SampleType,
+    pub big_endian: bool,
+    pub channels: usize,
+    pub stride: usize,
+}
+
+impl Format {
+    fn frame_size(&self) -> usize {
+        self.channels * self.sample_type.size()
+    }
+}
+
+/// A file waiting for its layout to be described before it is loaded.
+#[derive(Debug)]
+pub struct Import {
+    pub path: String,
+    pub format: Format,
+
+    /// Serve the samples from a memory mapping instead of loading them.
+    pub map: bool,
+}
+
+impl Import {
+    pub fn new(path: &str) -> Import {
+        Import {
+            path: path.to_owned(),
+            format: Format {
+                header: 0,
+                sample_type: SampleType::I16,
+                big_endian: false,
+                channels: 1,
+                stride: 2,
+            },
+            map: false,
+        }
+    }
+
+    /// Shows the layout window. Returns Some(true) when the file should be
+    /// loaded and Some(false) when the import was cancelled.
+    pub fn window(&mut self, ui: &Ui) -> Option<bool> {
+        let mut result = None;
+        let path = &self.path;
+        let format = &mut self.format;
+        let map = &mut self.map;
+
+        ui.window(im_str!("Binary import"))
+            .size((360.0, 240.0), ImGuiCond::FirstUseEver)
+            .position((100.0, 80.0), ImGuiCond::FirstUseEver)
+            .collapsible(false)
+            .build(|| {
+                ui.text(path);
+
+                let packed = format.stride == format.frame_size();
+
+                let mut header = format.header as i32;
+                if ui.input_int(im_str!("Header bytes"), &mut header).build() {
+                    format.header = max(header, 0) as usize;
+                }
+
+                let mut sample_type = SAMPLE_TYPES
+                    .iter()
+                    .position(|&t| t == format.sample_type)
+                    .unwrap_or(0) as i32;
+                if ui.combo(
+                    im_str!("Sample type"),
+                    &mut sample_type,
+                    &[
+                        im_str!("u8"),
+                        im_str!("i16"),
+                        im_str!("u16"),
+                        im_str!("i32"),
+                        im_str!("f32"),
+                        im_str!("f64"),
+                        im_str!("i8"),
+                        im_str!("u32"),
+                        im_str!("i64"),
+                        im_str!("u64"),
+                    ],
+                    10,
+                ) {
+                    format.sample_type = SAMPLE_TYPES[sample_type as usize];
+                }
+
+                let mut endian = format.big_endian as i32;
+                if ui.combo(
+                    im_str!("Endianness"),
+                    &mut endian,
+                    &[im_str!("Little"), im_str!("Big")],
+                    2,
+                ) {
+                    format.big_endian = endian == 1;
+                }
+
+                let mut channels = format.channels as i32;
+                if ui.input_int(im_str!("Channels"), &mut channels).build() {
+                    format.channels = max(channels, 1) as usize;
+                }
+
+                // Keep packed frames packed when the sample layout changes
+                if packed {
+                    format.stride = format.frame_size();
+                }
+
+                let mut stride = format.stride as i32;
+                if ui.input_int(im_str!("Stride bytes"), &mut stride).build() {
+                    format.stride = max(stride, 0) as usize;
+                }
+                format.stride = format.stride.max(format.frame_size());
+
+                ui.checkbox(im_str!("Memory map"), map);
+
+                if ui.button(im_str!("Load"), (0.0, 0.0)) {
+                    result = Some(true);
+                }
+                ui.same_line(0.0);
+                if ui.button(im_str!("Cancel"), (0.0, 0.0)) {
+                    result = Some(false);
+                }
+            });
+
+        result
+    }
+}
+
+/// Reads the file into one block list per channel. A partial frame at the end
+/// of the file is dropped.
+pub fn load(
+    path: &str,
+    format: &Format,
+    channels: &[Arc<Mutex<Vec<Box<Block>>>>],
+    stop_loading: &AtomicBool,
+    waker: &Waker,
+    report: &Arc<Mutex<Option<LoadReport>>>,
+) -> io::Result<()> {
+    let mut reader = BufReader::new(ProgressReader::open(path, report)?);
+
+    io::copy(&mut (&mut reader).take(format.header as u64), &mut io::sink())?;
+
+    let size = format.sample_type.size();
+    let mut frame = vec![0; format.stride];
+    let mut blocks = channels.iter().map(|_| Box::new(Block::new())).collect::<Vec<_>>();
+
+    while !stop_loading.load(Ordering::SeqCst) {
+        match reader.read_exact(&mut frame) {
+            Ok(()) => (),
+            Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        }
+
+        if blocks.first().map_or(false, |block| block.data0.is_full()) {
+            for (block, target) in blocks.iter_mut().zip(channels.iter()) {
+                let full = ::std::mem::replace(block, Box::new(Block::new()));
+                target.lock().unwrap().push(full);
+            }
+
+            waker.wake();
+        }
+
+        for (i, block) in blocks.iter_mut().enumerate() {
+            let bytes = &frame[i * size..(i + 1) * size];
+
+            block.push(format.sample_type.read(bytes, format.big_endian));
+        }
+    }
+
+    for (block, target) in blocks.into_iter().zip(channels.iter()) {
+        if block.data0.len() != 0 {
+            target.lock().unwrap().push(block);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use {Decoder, PortSettings, Source};
+
+pub const USAGE: &str = "Usage: plotter-rs [OPTIONS] [FILE]...
+
+Opens each FILE, adding its channels to those of the files before it. A FILE
+of - reads lines from standard input.
+
+Options:
+    --port NAME          Open a serial port after the files
+    --baud RATE          Baud rate of the serial port [default: 250000]
+    --decoder DECODER    How the serial port is read: latency or lines
+                         [default: latency]
+    --session FILE       Load channel properties and the view from a session
+    --follow             Keep reading lines appended to the last text file
+    --view START:END     Show the samples from START to END
+    -h, --help           Print this help
+
+--baud and --decoder without --port use the first of COM1 to COM30 that
+opens. Standard input and the serial port keep loading until they close, so
+they should be given last.";
+
+#[derive(Debug)]
+pub struct Options {
+    /// Files, standard input and the serial port, in the order they are opened.
+    pub sources: Vec<Source>,
+    pub session: Option<String>,
+    pub follow: bool,
+    pub view: Option<(f64, f64)>,
+    pub help: bool,
+}
+
+pub fn parse<I: Iterator<Item = String>>(args: I) -> Result<Options, String> {
+    let mut args = args;
+    let mut options = Options {
+        sources: Vec::new(),
+        session: None,
+        follow: false,
+        view: None,
+        help: false,
+    };
+    let mut port: Option<PortSettings> = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => options.help = true,
+            "--follow" => options.follow = true,
+            "--port" => {
+                port.get_or_insert_with(PortSettings::new).name = Some(value(&mut args, &arg)?);
+            }
+            "--baud" => {
+                let baud = value(&mut args, &arg)?;
+                port.get_or_insert_with(PortSettings::new).baud_rate =
+                    baud.parse().map_err(|_| format!("Invalid baud rate {}", baud))?;
+            }
+            "--decoder" => {
+                port.get_or_insert_with(PortSettings::new).decoder = match value(&mut args, &arg)?.as_str() {
+                    "latency" => Decoder::Latency,
+                    "lines" => Decoder::Lines,
+                    decoder => return Err(format!("Unknown decoder {}", decoder)),
+                };
+            }
+            "--session" => options.session = Some(value(&mut args, &arg)?),
+            "--view" => options.view = Some(parse_range(&value(&mut args, &arg)?)?),
+            "-" => options.sources.push(Source::Stdin),
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+            _ => options.sources.push(Source::File(arg)),
+        }
+    }
+
+    if let Some(port) = port {
+        options.sources.push(Source::Port(port));
+    }
+
+    Ok(options)
+}
+
+fn value<I: Iterator<Item = String>>(args: &mut I, option: &str) -> Result<String, String> {
+    args.next().ok_or_else(|| format!("Missing value for {}", option))
+}
+
+/// A range like `1000:5000`.
+fn parse_range(range: &str) -> Result<(f64, f64), String> {
+    let mut parts = range.splitn(2, ':');
+    let start = parts.next().and_then(|start| start.trim().parse::<f64>().ok());
+    let end = parts.next().and_then(|end| end.trim().parse::<f64>().ok());
+
+    match (start, end) {
+        (Some(start), Some(end)) if end > start => Ok((start, end)),
+        _ => Err(format!("Invalid range {}, expected START:END", range)),
+    }
+}
@@ -0,0 +1,408 @@
+use imgui::{ImGuiCond, ImStr, ImString, Ui};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use notify::BadLines;
+use text::parse_value;
+use {Block, LoadReport, ProgressReader, Waker};
+
+/// Number of lines read for detection and shown in the preview.
+const PREVIEW_LINES: usize = 20;
+
+const DELIMITERS: [u8; 4] = [b',', b'\t', b';', b' '];
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Role {
+    Time,
+    Value,
+    Ignore,
+}
+
+#[derive(Debug, Clone)]
+pub struct Column {
+    pub name: String,
+    pub role: Role,
+}
+
+#[derive(Debug, Clone)]
+pub struct Format {
+    pub delimiter: u8,
+
+    /// Lines before the header, or before the data if there is no header.
+    pub preamble: usize,
+    pub header: bool,
+    pub columns: Vec<Column>,
+}
+
+impl Format {
+    pub fn value_columns(&self) -> Vec<&Column> {
+        self.columns
+            .iter()
+            .filter(|column| column.role == Role::Value)
+            .collect()
+    }
+
+    pub fn has_time(&self) -> bool {
+        self.columns.iter().any(|column| column.role == Role::Time)
+    }
+}
+
+/// The first lines of a file together with the detected format, shown before
+/// the file is loaded.
+#[derive(Debug)]
+pub struct Preview {
+    pub path: String,
+    pub format: Format,
+    lines: Vec<String>,
+}
+
+impl Preview {
+    pub fn read(path: &str) -> io::Result<Preview> {
+        let reader = BufReader::new(File::open(path)?);
+
+        let lines = reader
+            .lines()
+            .take(PREVIEW_LINES)
+            .collect::<io::Result<Vec<_>>>()?
+            .into_iter()
+            .filter(|line| !line.trim().is_empty())
+            .collect::<Vec<_>>();
+
+        let delimiter = detect_delimiter(&lines);
+        let header = detect_header(&lines, delimiter);
+        let columns = detect_columns(&lines, delimiter, header);
+
+        Ok(Preview {
+            path: path.to_owned(),
+            format: Format {
+                delimiter: delimiter,
+                preamble: 0,
+                header: header,
+                columns: columns,
+            },
+            lines: lines,
+        })
+    }
+
+    /// Whether the file has more than the single plain column `open_file` reads.
+    pub fn is_table(&self) -> bool {
+        self.format.header || self.format.columns.len() > 1
+    }
+
+    /// Shows the preview window. Returns Some(true) when the file should be
+    /// loaded and Some(false) when the import was cancelled.
+    pub fn window(&mut self, ui: &Ui) -> Option<bool> {
+        let mut result = None;
+
+        ui.window(im_str!("CSV import"))
+            .size((520.0, 400.0), ImGuiCond::FirstUseEver)
+            .position((100.0, 80.0), ImGuiCond::FirstUseEver)
+            .collapsible(false)
+            .build(|| {
+                ui.text(&self.path);
+
+                let mut redetect = false;
+
+                let mut delimiter = DELIMITERS
+                    .iter()
+                    .position(|&d| d == self.format.delimiter)
+                    .unwrap_or(0) as i32;
+                if ui.combo(
+                    im_str!("Delimiter"),
+                    &mut delimiter,
+                    &[im_str!("Comma"), im_str!("Tab"), im_str!("Semicolon"), im_str!("Space")],
+                    4,
+                ) {
+                    self.format.delimiter = DELIMITERS[delimiter as usize];
+                    redetect = true;
+                }
+
+                if ui.checkbox(im_str!("Header row"), &mut self.format.header) {
+                    redetect = true;
+                }
+
+                if redetect {
+                    self.format.columns =
+                        detect_columns(&self.lines, self.format.delimiter, self.format.header);
+                }
+
+                ui.separator();
+
+                for (i, column) in self.format.columns.iter_mut().enumerate() {
+                    ui.with_id(i as i32, || {
+                        let mut role = column.role as i32;
+                        let items: [&ImStr; 3] = [im_str!("Time"), im_str!("Value"), im_str!("Ignore")];
+
+                        ui.with_item_width(100.0, || {
+                            if ui.combo(im_str!("##role"), &mut role, &items, 3) {
+                                column.role = match role {
+                                    0 => Role::Time,
+                                    1 => Role::Value,
+                                    _ => Role::Ignore,
+                                };
+                            }
+                        });
+                        ui.same_line(0.0);
+                        ui.text(&column.name);
+                    });
+                }
+
+                // Only one column can be the time column
+                if let Some(time) = self.format.columns.iter().rposition(|c| c.role == Role::Time) {
+                    for (i, column) in self.format.columns.iter_mut().enumerate() {
+                        if i != time && column.role == Role::Time {
+                            column.role = Role::Value;
+                        }
+                    }
+                }
+
+                ui.separator();
+
+                for line in &self.lines {
+                    ui.text(ImString::new(line.clone()));
+                }
+
+                ui.separator();
+
+                let any = !self.format.value_columns().is_empty();
+
+                if !any {
+                    ui.text_colored((1.0, 0.8, 0.2, 1.0), im_str!("Set at least one column to Value"));
+                }
+
+                if ui.button(im_str!("Load"), (0.0, 0.0)) && any {
+                    result = Some(true);
+                }
+                ui.same_line(0.0);
+                if ui.button(im_str!("Cancel"), (0.0, 0.0)) {
+                    result = Some(false);
+                }
+            });
+
+        result
+    }
+}
+
+/// Splits a line into trimmed fields, dropping surrounding quotes.
+pub fn split(line: &str, delimiter: u8) -> Vec<&str> {
+    let fields = if delimiter == b' ' {
+        line.split_whitespace().collect::<Vec<_>>()
+    } else {
+        line.split(delimiter as char).collect::<Vec<_>>()
+    };
+
+    fields
+        .into_iter()
+        .map(|field| field.trim().trim_matches('"').trim())
+        .collect()
+}
+
+/// Whether every line is a single number with a decimal comma, like `12,5`.
+fn decimal_commas(lines: &[String]) -> bool {
+    let digits = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+
+    !lines.is_empty()
+        && lines.iter().all(|line| {
+            let line = line.trim().trim_start_matches(|c| c == '-' || c == '+');
+            let mut parts = line.split(',');
+
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(whole), Some(fraction), None) => digits(whole) && digits(fraction),
+                _ => false,
+            }
+        })
+}
+
+/// The delimiter that splits the lines into the most columns, as long as all
+/// lines agree on the count. Lines of numbers with decimal commas are never
+/// split on the comma.
+fn detect_delimiter(lines: &[String]) -> u8 {
+    let decimal_commas = decimal_commas(lines);
+    let mut best = (if decimal_commas { b';' } else { b',' }, 1);
+
+    for &delimiter in &DELIMITERS {
+        if delimiter == b',' && decimal_commas {
+            continue;
+        }
+
+        let counts = lines
+            .iter()
+            .map(|line| split(line, delimiter).len())
+            .collect::<Vec<_>>();
+
+        if let Some(&count) = counts.first() {
+            if count > best.1 && counts.iter().all(|&c| c == count) {
+                best = (delimiter, count);
+            }
+        }
+    }
+
+    best.0
+}
+
+/// A header is a first line with text where the following lines have numbers.
+fn detect_header(lines: &[String], delimiter: u8) -> bool {
+    let numeric = |line: &String| {
+        split(line, delimiter)
+            .iter()
+            .filter(|field| parse_value(field.as_bytes()).is_some())
+            .count()
+    };
+
+    match (lines.get(0), lines.get(1)) {
+        (Some(first), Some(second)) => numeric(first) < numeric(second),
+        (Some(first), None) => numeric(first) == 0,
+        _ => false,
+    }
+}
+
+/// Numeric columns become values, a numeric column named like a time becomes
+/// the time column and everything else is ignored.
+fn detect_columns(lines: &[String], delimiter: u8, header: bool) -> Vec<Column> {
+    let names = match (header, lines.get(0)) {
+        (true, Some(first)) => split(first, delimiter)
+            .iter()
+            .map(|name| name.to_string())
+            .collect(),
+        _ => Vec::new(),
+    };
+    let rows = lines
+        .iter()
+        .skip(if header { 1 } else { 0 })
+        .map(|line| split(line, delimiter))
+        .collect::<Vec<_>>();
+
+    let count = rows.iter().map(|row| row.len()).chain(Some(names.len())).max().unwrap_or(0);
+    let mut has_time = false;
+
+    (0..count)
+        .map(|i| {
+            let name = names.get(i).cloned().unwrap_or_else(|| format!("Col {}", i));
+            let numeric = rows
+                .iter()
+                .filter_map(|row| row.get(i))
+                .all(|field| parse_value(field.as_bytes()).is_some());
+
+            let lower = name.to_lowercase();
+            let time_name = lower == "t" || lower.contains("time") || lower.starts_with("sec");
+
+            let role = if !numeric {
+                Role::Ignore
+            } else if time_name && !has_time {
+                has_time = true;
+                Role::Time
+            } else {
+                Role::Value
+            };
+
+            Column {
+                name: name,
+                role: role,
+            }
+        })
+        .collect()
+}
+
+/// Reads the file into one block list per value column and the time column
+/// into time. Fields that are not numbers leave a gap, and their lines are
+/// returned.
+pub fn load(
+    path: &str,
+    format: &Format,
+    channels: &[Arc<Mutex<Vec<Box<Block>>>>],
+    time: Option<&Arc<Mutex<Vec<Box<Block>>>>>,
+    stop_loading: &AtomicBool,
+    waker: &Waker,
+    report: &Arc<Mutex<Option<LoadReport>>>,
+) -> io::Result<BadLines> {
+    let reader = BufReader::new(ProgressReader::open(path, report)?);
+
+    // Where each column goes: Some(None) for time, Some(Some(i)) for channel i
+    let mut targets = Vec::new();
+    let mut next_channel = 0;
+    for column in &format.columns {
+        targets.push(match column.role {
+            Role::Time => Some(None),
+            Role::Value => {
+                next_channel += 1;
+                Some(Some(next_channel - 1))
+            }
+            Role::Ignore => None,
+        });
+    }
+
+    let mut blocks = channels.iter().map(|_| Box::new(Block::new())).collect::<Vec<_>>();
+    let mut time_block = Box::new(Block::new());
+    let mut bad_lines = BadLines::new();
+
+    let skip = format.preamble + if format.header { 1 } else { 0 };
+
+    for (i, line) in reader.lines().enumerate().skip(skip) {
+        if stop_loading.load(Ordering::SeqCst) {
+            break;
+        }
+
+        let line = line?;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let fields = split(&line, format.delimiter);
+
+        // Every row adds one value to each block, so they fill up together
+        if blocks.iter().chain(Some(&time_block)).any(|block| block.data0.is_full()) {
+            for (block, target) in blocks.iter_mut().zip(channels.iter()) {
+                let full = ::std::mem::replace(block, Box::new(Block::new()));
+                target.lock().unwrap().push(full);
+            }
+
+            if let Some(time) = time {
+                let full = ::std::mem::replace(&mut time_block, Box::new(Block::new()));
+                time.lock().unwrap().push(full);
+            }
+
+            waker.wake();
+        }
+
+        let mut bad = false;
+
+        for (column, target) in targets.iter().enumerate() {
+            if target.is_none() {
+                continue;
+            }
+
+            let field = fields.get(column).cloned().unwrap_or("");
+            let value = parse_value(field.as_bytes()).unwrap_or_else(|| {
+                bad |= !field.is_empty();
+                ::std::f64::NAN
+            });
+
+            match *target {
+                Some(Some(channel)) => blocks[channel].push(value),
+                Some(None) => time_block.push(value),
+                None => (),
+            }
+        }
+
+        if bad {
+            bad_lines.add(i + 1, line.as_bytes());
+        }
+    }
+
+    for (block, target) in blocks.into_iter().zip(channels.iter()) {
+        if block.data0.len() != 0 {
+            target.lock().unwrap().push(block);
+        }
+    }
+
+    if let Some(time) = time {
+        if time_block.data0.len() != 0 {
+            time.lock().unwrap().push(time_block);
+        }
+    }
+
+    Ok(bad_lines)
+}
@@ -0,0 +1,248 @@
+use glium::backend::Facade;
+use glium::texture::{RawImage2d, Texture2d};
+use imgui::ImTexture;
+use imgui_glium_renderer::Textures;
+use std::cmp::max;
+use std::fmt;
+
+use {Channel, Lane};
+
+/// Most samples that are looked at in one screen column.
+const MAX_SAMPLES_PER_COLUMN: usize = 64;
+
+/// Accumulates how often the traces pass through each pixel, like the
+/// persistence of an analog scope.
+pub struct Density {
+    pub enabled: bool,
+
+    /// Part of the accumulated hits that fades away every accumulation.
+    pub decay: f32,
+
+    width: usize,
+    height: usize,
+
+    /// One histogram per channel.
+    hits: Vec<Vec<f32>>,
+
+    pixels: Vec<u8>,
+    dirty: bool,
+
+    texture: Option<ImTexture>,
+
+    /// Scope sweep that was accumulated last.
+    last_sweep: usize,
+}
+
+impl Density {
+    pub fn new() -> Density {
+        Density {
+            enabled: false,
+            decay: 0.2,
+            width: 0,
+            height: 0,
+            hits: Vec::new(),
+            pixels: Vec::new(),
+            dirty: false,
+            texture: None,
+            last_sweep: 0,
+        }
+    }
+
+    pub fn texture(&self) -> Option<ImTexture> {
+        self.texture
+    }
+
+    pub fn clear(&mut self) {
+        for hits in &mut self.hits {
+            for hit in hits.iter_mut() {
+                *hit = 0.0;
+            }
+        }
+    }
+
+    /// Fades the histogram and adds the samples currently in view. With a scope
+    /// sweep number this only happens once per sweep.
+    pub fn accumulate(
+        &mut self,
+        sweep: Option<usize>,
+        channels: &[Channel],
+        shown: &[bool],
+        lanes: &[Lane],
+        origin: f64,
+        scale: f64,
+        y_scale: f64,
+        view_size: (f32, f32),
+    ) {
+        if let Some(sweep) = sweep {
+            if sweep == self.last_sweep {
+                return;
+            }
+            self.last_sweep = sweep;
+        }
+
+        let (width, height) = (view_size.0 as usize, view_size.1 as usize);
+
+        if width != self.width || height != self.height || channels.len() != self.hits.len() {
+            self.width = width;
+            self.height = height;
+            self.hits = vec![vec![0.0; width * height]; channels.len()];
+        }
+
+        let retain = 1.0 - self.decay.max(0.0).min(1.0);
+
+        for (((channel, lane), &shown), hits) in channels
+            .iter()
+            .zip(lanes.iter())
+            .zip(shown.iter())
+            .zip(self.hits.iter_mut())
+        {
+            let retain = if shown { retain } else { 0.0 };
+
+            for hit in hits.iter_mut() {
+                *hit *= retain;
+            }
+
+            if !shown {
+                continue;
+            }
+
+            let blocks = channel.samples();
+            let count = blocks.sample_count();
+
+            let top = lane.top.max(0.0) as usize;
+            let bottom = ((lane.top + lane.height).min(height as f64) as usize).saturating_sub(1);
+
+            let mut last_y = None;
+
+            for x in 0..width {
+                let start = origin + scale * x as f64;
+                let end = origin + scale * (x + 1) as f64;
+
+                if end < 0.0 || start >= count as f64 {
+                    last_y = None;
+                    continue;
+                }
+
+                let start = start.max(0.0) as usize;
+                let end = max(end.min(count as f64) as usize, start + 1);
+                let step = max((end - start) / MAX_SAMPLES_PER_COLUMN, 1);
+
+                for i in (start..end).step_by(step) {
+                    let value = blocks.sample(i);
+                    let y = channel.value_to_y(value, lane.center_y, y_scale);
+
+                    let from = last_y.unwrap_or(y);
+                    add_span(hits, width, x, from, y, top, bottom);
+
+                    last_y = Some(y);
+                }
+            }
+        }
+
+        self.colorize(channels);
+    }
+
+    /// Log scaled intensity, tinted with the channel colors.
+    fn colorize(&mut self, channels: &[Channel]) {
+        self.pixels.clear();
+        self.pixels.resize(self.width * self.height * 4, 0);
+
+        for (channel, hits) in channels.iter().zip(self.hits.iter()) {
+            let max_hits = hits.iter().fold(0.0f32, |a, &b| a.max(b));
+
+            if max_hits <= 0.0 {
+                continue;
+            }
+
+            let norm = (1.0 + max_hits).ln();
+            let color = [
+                channel.color[0] * 255.0,
+                channel.color[1] * 255.0,
+                channel.color[2] * 255.0,
+            ];
+
+            // The image is drawn with v = 0 at the top, so rows stay in screen order
+            for y in 0..self.height {
+                let row = y * self.width;
+
+                for x in 0..self.width {
+                    let hit = hits[y * self.width + x];
+
+                    if hit <= 0.0 {
+                        continue;
+                    }
+
+                    let intensity = (1.0 + hit).ln() / norm;
+                    let pixel = &mut self.pixels[(row + x) * 4..(row + x) * 4 + 4];
+
+                    for c in 0..3 {
+                        let value = pixel[c] as f32 + color[c] * intensity;
+                        pixel[c] = value.min(255.0) as u8;
+                    }
+                    pixel[3] = max(pixel[3], (255.0 * intensity) as u8);
+                }
+            }
+        }
+
+        self.dirty = true;
+    }
+
+    /// Uploads the colorized histogram if it has changed since the last upload.
+    pub fn upload<F: Facade>(&mut self, facade: &F, textures: &mut Textures) {
+        if !self.dirty || self.width == 0 || self.height == 0 {
+            return;
+        }
+
+        self.dirty = false;
+
+        let image = RawImage2d::from_raw_rgba(
+            self.pixels.clone(),
+            (self.width as u32, self.height as u32),
+        );
+
+        if let Ok(texture) = Texture2d::new(facade, image) {
+            match self.texture {
+                Some(id) => {
+                    textures.replace(id, texture);
+                }
+                None => self.texture = Some(textures.insert(texture)),
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Density {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Density {{ enabled: {}, decay: {}, size: {}x{} }}",
+            self.enabled, self.decay, self.width, self.height
+        )
+    }
+}
+
+/// Adds one hit spread evenly over the pixels between y1 and y2 in column x.
+fn add_span(hits: &mut [f32], width: usize, x: usize, y1: f64, y2: f64, top: usize, bottom: usize) {
+    if !y1.is_finite() || !y2.is_finite() {
+        return;
+    }
+
+    let (low, high) = if y1 < y2 { (y1, y2) } else { (y2, y1) };
+
+    if high < top as f64 || low > bottom as f64 {
+        return;
+    }
+
+    let low = max(low.max(0.0) as usize, top);
+    let high = (high.max(0.0) as usize).min(bottom);
+
+    if low > high {
+        return;
+    }
+
+    let weight = 1.0 / (high - low + 1) as f32;
+
+    for y in low..=high {
+        hits[y * width + x] += weight;
+    }
+}
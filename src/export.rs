@@ -0,0 +1,135 @@
+use imgui::{ImGuiCond, ImStr, ImString, Ui};
+use std::cmp::{max, min};
+use std::fmt::Display;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use notify::Notifications;
+use {file_dialog, Channel, Waker};
+
+/// What differs between the file types channels can be exported to.
+pub trait Format: Send + 'static {
+    type Error: Display;
+
+    fn title(&self) -> &'static ImStr;
+
+    /// Filter of the save dialog.
+    fn extension(&self) -> &'static str;
+
+    /// Shows the controls for the options of the format.
+    fn options(&mut self, ui: &Ui);
+
+    /// Writes rows samples of the copied channels. Channels that end early
+    /// are padded.
+    fn write(&self, path: &str, selected: &[Vec<f64>], rows: usize) -> Result<(), Self::Error>;
+}
+
+/// Which channels and samples to export, and how.
+#[derive(Debug)]
+pub struct Export<F> {
+    pub selected: Vec<bool>,
+    pub start: i32,
+    pub end: i32,
+    pub format: F,
+}
+
+impl<F: Format> Export<F> {
+    /// Starts out with the shown channels and the range in view.
+    pub fn new(shown: Vec<bool>, start: f64, end: f64, count: usize, format: F) -> Export<F> {
+        let count = min(count, ::std::i32::MAX as usize) as i32;
+
+        Export {
+            selected: shown,
+            start: min(max(start as i32, 0), count),
+            end: min(max(end as i32, 0), count),
+            format: format,
+        }
+    }
+
+    /// Shows the export window. Returns Some(true) when the file should be
+    /// written and Some(false) when the export was cancelled.
+    fn window(&mut self, ui: &Ui, channels: &[Channel]) -> Option<bool> {
+        let mut result = None;
+
+        ui.window(self.format.title())
+            .size((320.0, 300.0), ImGuiCond::FirstUseEver)
+            .position((100.0, 80.0), ImGuiCond::FirstUseEver)
+            .collapsible(false)
+            .build(|| {
+                for (i, (channel, selected)) in channels.iter().zip(self.selected.iter_mut()).enumerate() {
+                    ui.with_id(i as i32, || {
+                        ui.checkbox(&ImString::new(channel.name.clone()), selected);
+                    });
+                }
+
+                ui.separator();
+
+                ui.input_int(im_str!("First sample"), &mut self.start).build();
+                ui.input_int(im_str!("End sample"), &mut self.end).build();
+                self.format.options(ui);
+
+                self.start = max(self.start, 0);
+                self.end = max(self.end, self.start);
+
+                let any = self.selected.iter().any(|&selected| selected);
+
+                if ui.button(im_str!("Export"), (0.0, 0.0)) && any {
+                    result = Some(true);
+                }
+                ui.same_line(0.0);
+                if ui.button(im_str!("Cancel"), (0.0, 0.0)) {
+                    result = Some(false);
+                }
+            });
+
+        result
+    }
+
+    /// Copies the range of the selected channels, so that it can be written
+    /// by another thread.
+    fn copy(&self, channels: &[Channel]) -> Vec<Vec<f64>> {
+        channels
+            .iter()
+            .zip(self.selected.iter())
+            .filter(|&(_, &selected)| selected)
+            .map(|(channel, _)| channel.copy_range(self.start as usize, self.end as usize))
+            .collect()
+    }
+}
+
+/// Shows the export window while there is one. When it is confirmed, the
+/// selection is copied and written on another thread, and the outcome shown
+/// as a notification.
+pub fn show<F: Format>(
+    ui: &Ui,
+    export: &mut Option<Export<F>>,
+    channels: &[Channel],
+    notifications: &Arc<Mutex<Notifications>>,
+    waker: &Waker,
+) {
+    let write = match *export {
+        Some(ref mut export) => export.window(ui, channels),
+        None => None,
+    };
+
+    if let Some(write) = write {
+        if let Some(export) = export.take() {
+            if write {
+                if let Some(path) = file_dialog(notifications, export.format.extension(), true) {
+                    let selected = export.copy(channels);
+                    let rows = (export.end - export.start) as usize;
+                    let notifications = notifications.clone();
+                    let waker = waker.clone();
+
+                    thread::spawn(move || {
+                        match export.format.write(&path, &selected, rows) {
+                            Ok(()) => notifications.lock().unwrap().info(&path, "exported"),
+                            Err(e) => notifications.lock().unwrap().error(&path, e),
+                        }
+                        waker.wake_now();
+                    });
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+
+use csv::{split, Column, Format, Role};
+
+/// Lines searched for a known preamble.
+const PREAMBLE_LINES: usize = 40;
+
+/// A CSV export of a bench oscilloscope. The samples are loaded like any other
+/// CSV file, but the time base comes from the preamble.
+#[derive(Debug, Clone)]
+pub struct Capture {
+    pub format: Format,
+
+    /// Seconds between samples.
+    pub interval: f64,
+
+    /// Time of the first sample, usually negative with the trigger at zero.
+    pub start: f64,
+}
+
+/// Recognizes Rigol, Keysight and Tektronix exports.
+pub fn detect(path: &str) -> io::Result<Option<Capture>> {
+    let reader = BufReader::new(File::open(path)?);
+    let lines = reader
+        .lines()
+        .take(PREAMBLE_LINES)
+        .collect::<io::Result<Vec<_>>>()?;
+
+    if lines.len() < 3 {
+        return Ok(None);
+    }
+
+    Ok(rigol(&lines)
+        .or_else(|| keysight(&lines))
+        .or_else(|| tektronix(&lines))
+        .or_else(|| tektronix_legacy(&lines)))
+}
+
+fn fields(line: &str) -> Vec<&str> {
+    split(line, b',')
+}
+
+fn number(line: &str, column: usize) -> Option<f64> {
+    fields(line).get(column).and_then(|field| field.parse().ok())
+}
+
+/// The value after a `Key,value` line of the preamble.
+fn preamble_value<'a>(lines: &'a [String], key: &str) -> Option<&'a str> {
+    lines.iter().find_map(|line| {
+        let fields = fields(line);
+
+        if fields.first() == Some(&key) {
+            fields.get(1).cloned()
+        } else {
+            None
+        }
+    })
+}
+
+fn channel_name(name: &str, unit: Option<&str>) -> String {
+    let name = if name.chars().all(|c| c.is_ascii_digit()) {
+        format!("CH{}", name)
+    } else {
+        name.to_owned()
+    };
+
+    match unit {
+        Some(unit) if !unit.is_empty() => format!("{} ({})", name, unit),
+        _ => name,
+    }
+}
+
+/// `X,CH1,CH2,Start,Increment` followed by units and the time base on the
+/// second line. The X column is the sample index.
+fn rigol(lines: &[String]) -> Option<Capture> {
+    let names = fields(&lines[0]);
+
+    if names.first() != Some(&"X") {
+        return None;
+    }
+
+    let start_column = names.iter().position(|&name| name == "Start")?;
+    let increment_column = names.iter().position(|&name| name == "Increment")?;
+    let units = fields(&lines[1]);
+
+    let columns = names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| Column {
+            name: channel_name(name, units.get(i).cloned()),
+            role: if i > 0 && i < start_column {
+                Role::Value
+            } else {
+                Role::Ignore
+            },
+        })
+        .collect();
+
+    Some(Capture {
+        format: Format {
+            delimiter: b',',
+            preamble: 2,
+            header: false,
+            columns: columns,
+        },
+        interval: number(&lines[1], increment_column)?,
+        start: number(&lines[1], start_column)?,
+    })
+}
+
+/// `x-axis,1,2` followed by `second,Volt,Volt` and time in the first column.
+fn keysight(lines: &[String]) -> Option<Capture> {
+    let names = fields(&lines[0]);
+
+    if names.first().map(|name| name.to_lowercase()) != Some("x-axis".to_owned()) {
+        return None;
+    }
+
+    let units = fields(&lines[1]);
+    let start = number(&lines[2], 0)?;
+    let interval = number(lines.get(3)?, 0)? - start;
+
+    let columns = names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| Column {
+            name: channel_name(name, units.get(i).cloned()),
+            role: if i > 0 && !name.is_empty() {
+                Role::Value
+            } else {
+                Role::Ignore
+            },
+        })
+        .collect();
+
+    Some(Capture {
+        format: Format {
+            delimiter: b',',
+            preamble: 2,
+            header: false,
+            columns: columns,
+        },
+        interval: interval,
+        start: start,
+    })
+}
+
+/// `Key,value` preamble lines up to a `TIME,CH1,CH2` header, as written by
+/// the MSO and MDO series. A plain CSV with a TIME column has no preamble and
+/// keeps its own time column.
+fn tektronix(lines: &[String]) -> Option<Capture> {
+    let header = lines
+        .iter()
+        .position(|line| fields(line).first() == Some(&"TIME"))?;
+
+    let preamble = &lines[..header];
+    if preamble_value(preamble, "Record Length").is_none() && preamble_value(preamble, "Sample Interval").is_none() {
+        return None;
+    }
+
+    let names = fields(&lines[header]);
+    let unit = preamble_value(&lines[..header], "Vertical Units");
+
+    let start = number(lines.get(header + 1)?, 0)?;
+    let interval = match preamble_value(&lines[..header], "Sample Interval").and_then(|value| value.parse().ok()) {
+        Some(interval) => interval,
+        None => number(lines.get(header + 2)?, 0)? - start,
+    };
+
+    let columns = names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| Column {
+            name: channel_name(name, unit),
+            role: if i > 0 && !name.is_empty() {
+                Role::Value
+            } else {
+                Role::Ignore
+            },
+        })
+        .collect();
+
+    Some(Capture {
+        format: Format {
+            delimiter: b',',
+            preamble: header,
+            header: true,
+            columns: columns,
+        },
+        interval: interval,
+        start: start,
+    })
+}
+
+/// One channel per file with the preamble in the first three columns and
+/// time and value in the fourth and fifth, as written by the TDS series.
+fn tektronix_legacy(lines: &[String]) -> Option<Capture> {
+    if fields(&lines[0]).first() != Some(&"Record Length") {
+        return None;
+    }
+
+    let source = preamble_value(lines, "Source").unwrap_or("CH");
+    let unit = preamble_value(lines, "Vertical Units");
+    let interval = preamble_value(lines, "Sample Interval")?.parse().ok()?;
+
+    let mut columns = (0..4)
+        .map(|_| Column {
+            name: String::new(),
+            role: Role::Ignore,
+        })
+        .collect::<Vec<_>>();
+    columns.push(Column {
+        name: channel_name(source, unit),
+        role: Role::Value,
+    });
+
+    Some(Capture {
+        format: Format {
+            delimiter: b',',
+            preamble: 0,
+            header: false,
+            columns: columns,
+        },
+        interval: interval,
+        start: number(&lines[0], 3)?,
+    })
+}
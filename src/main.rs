@@ -1,61 +1,128 @@
 extern crate arrayvec;
 extern crate byteorder;
 extern crate clamp;
+extern crate flate2;
 extern crate glium;
+extern crate hound;
 extern crate nfd;
+extern crate num_cpus;
 extern crate serialport;
 extern crate time;
+extern crate xz2;
+extern crate zip;
+extern crate zstd;
 extern crate image;
 
 #[macro_use]
 extern crate imgui;
 extern crate imgui_glium_renderer;
+extern crate memmap;
 
 #[cfg(windows)]
 extern crate winapi;
 
 use byteorder::{ByteOrder, LittleEndian};
 use glium::glutin::{
-    dpi::LogicalPosition, dpi::LogicalSize, Api, ContextBuilder, EventsLoop, GlContext, GlProfile,
-    GlRequest, WindowBuilder, Icon,
+    dpi::LogicalPosition, dpi::LogicalSize, Api, ContextBuilder, ControlFlow, Event, EventsLoop,
+    EventsLoopProxy, GlContext, GlProfile, GlRequest, WindowBuilder, Icon,
 };
 use glium::{Display, Surface};
-use imgui::{FrameSize, ImGui, ImGuiCond, ImGuiKey, ImVec2, StyleVar, Ui};
+use imgui::{FrameSize, ImGui, ImGuiCond, ImGuiKey, ImString, ImVec2, StyleVar, Ui, WindowDrawList};
 use imgui_glium_renderer::Renderer;
-use std::cmp::max;
+use std::cmp::{max, min};
+use std::collections::VecDeque;
 use std::fmt;
+use std::ops::Deref;
 use std::fs::File;
-use std::io::{BufRead, BufReader, ErrorKind, Read};
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, MutexGuard};
 use std::thread;
 use std::time::Duration;
 use clamp::clamp;
 
+mod binary;
+mod cli;
+mod csv;
+mod density;
+mod export;
+mod instrument;
+mod mapped;
+mod notify;
+mod npy;
+mod overview;
+mod scope;
+mod session;
+mod sigrok;
+mod text;
 mod timer;
+mod vcd;
+mod wav;
 
 struct Block {
     data0: arrayvec::ArrayVec<[f64; 32]>,
+    min: f64,
+    max: f64,
 }
 
 impl Block {
     fn new() -> Block {
         Block {
             data0: arrayvec::ArrayVec::new(),
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
         }
     }
 
     fn push(&mut self, val: f64) {
         self.data0.push(val);
+        self.min = self.min.min(val);
+        self.max = self.max.max(val);
     }
 
     fn lookup(&self, x: f64, _zoom: f64) -> Option<f64> {
         self.data0.get((x as i32 % 32) as usize).map(|p| *p)
     }
+
+    fn min_max(&self, start: usize, end: usize) -> Option<(f64, f64)> {
+        if start == 0 && end >= self.data0.len() {
+            if self.min <= self.max {
+                return Some((self.min, self.max));
+            } else {
+                return None;
+            }
+        }
+
+        let end = min(end, self.data0.len());
+
+        if start >= end {
+            return None;
+        }
+
+        self.data0[start..end]
+            .iter()
+            .fold(None, |range, &val| merge_range(range, Some((val, val))))
+    }
+}
+
+fn merge_range(a: Option<(f64, f64)>, b: Option<(f64, f64)>) -> Option<(f64, f64)> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some((a.0.min(b.0), a.1.max(b.1))),
+        (Some(a), None) => Some(a),
+        (None, b) => b,
+    }
 }
 
 trait Lookup {
     fn lookup(&self, x: f64, zoom: f64) -> Option<f64>;
+
+    /// Min and max of all samples in the range [start, end).
+    fn min_max(&self, start: f64, end: f64) -> Option<(f64, f64)>;
+
+    fn sample_count(&self) -> usize;
+
+    /// Sample i, which must be less than sample_count.
+    fn sample(&self, i: usize) -> f64;
 }
 
 impl Lookup for [Box<Block>] {
@@ -63,6 +130,128 @@ impl Lookup for [Box<Block>] {
         self.get((x as i32 / 32) as usize)
             .and_then(|block| block.lookup(x, zoom))
     }
+
+    fn min_max(&self, start: f64, end: f64) -> Option<(f64, f64)> {
+        let start = start.max(0.0) as usize;
+        let end = min(end.max(0.0).ceil() as usize, self.len() * 32);
+
+        let mut range = None;
+        let mut i = start;
+
+        while i < end {
+            let block_start = i % 32;
+            let block_end = block_start + (end - i);
+
+            range = merge_range(range, self[i / 32].min_max(block_start, block_end));
+
+            i += 32 - block_start;
+        }
+
+        range
+    }
+
+    fn sample_count(&self) -> usize {
+        self.last()
+            .map(|block| (self.len() - 1) * 32 + block.data0.len())
+            .unwrap_or(0)
+    }
+
+    fn sample(&self, i: usize) -> f64 {
+        self[i / 32].data0[i % 32]
+    }
+}
+
+impl Lookup for Vec<Box<Block>> {
+    fn lookup(&self, x: f64, zoom: f64) -> Option<f64> {
+        self[..].lookup(x, zoom)
+    }
+
+    fn min_max(&self, start: f64, end: f64) -> Option<(f64, f64)> {
+        self[..].min_max(start, end)
+    }
+
+    fn sample_count(&self) -> usize {
+        self[..].sample_count()
+    }
+
+    fn sample(&self, i: usize) -> f64 {
+        self[..].sample(i)
+    }
+}
+
+/// The samples of a channel, wherever they are stored.
+enum Samples<'a> {
+    Blocks(MutexGuard<'a, Vec<Box<Block>>>),
+    Mapped(&'a mapped::Mapped),
+}
+
+impl<'a> Deref for Samples<'a> {
+    type Target = dyn Lookup + 'a;
+
+    fn deref(&self) -> &(dyn Lookup + 'a) {
+        match *self {
+            Samples::Blocks(ref blocks) => &**blocks,
+            Samples::Mapped(mapped) => mapped,
+        }
+    }
+}
+
+/// Number of frames drawn after an event, so that imgui can settle.
+const REDRAW_FRAMES: u32 = 3;
+
+/// Lets loading threads wake the main loop when new data has arrived.
+#[derive(Clone)]
+struct Waker {
+    proxy: Option<EventsLoopProxy>,
+    last_wake: Arc<Mutex<f64>>,
+
+    /// Set while a wake is scheduled for the end of the current window.
+    trailing: Arc<AtomicBool>,
+}
+
+impl Waker {
+    fn new(proxy: Option<EventsLoopProxy>) -> Waker {
+        Waker {
+            proxy: proxy,
+            last_wake: Arc::new(Mutex::new(0.0)),
+            trailing: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Wakes the main loop at most 120 times per second. A wake inside the
+    /// window is delayed to its end, so the newest data is always drawn.
+    fn wake(&self) {
+        let now = time::precise_time_s();
+        let mut last_wake = self.last_wake.lock().unwrap();
+        let delay = *last_wake + 1.0 / 120.0 - now;
+
+        if delay <= 0.0 {
+            *last_wake = now;
+            self.wake_now();
+        } else if !self.trailing.swap(true, Ordering::SeqCst) {
+            let waker = self.clone();
+
+            thread::spawn(move || {
+                thread::sleep(Duration::from_micros((delay * 1e6) as u64));
+
+                *waker.last_wake.lock().unwrap() = time::precise_time_s();
+                waker.trailing.store(false, Ordering::SeqCst);
+                waker.wake_now();
+            });
+        }
+    }
+
+    fn wake_now(&self) {
+        if let Some(ref proxy) = self.proxy {
+            let _ = proxy.wakeup();
+        }
+    }
+}
+
+impl fmt::Debug for Waker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Waker {{ connected: {} }}", self.proxy.is_some())
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -82,34 +271,446 @@ impl MouseState {
     }
 }
 
+struct Channel {
+    name: String,
+    color: [f32; 4],
+    line_width: f32,
+    visible: bool,
+    solo: bool,
+    blocks: Arc<Mutex<Vec<Box<Block>>>>,
+
+    /// Memory mapped samples, used instead of blocks when set.
+    mapped: Option<Arc<mapped::Mapped>>,
+
+    points: Vec<ImVec2>,
+    pan: f32,
+    scale: f32,
+    lane_weight: f32,
+    style: DrawStyle,
+    digital: bool,
+
+    /// Min and max of every SUMMARY_BLOCKS complete blocks.
+    summary: Vec<Option<(f64, f64)>>,
+}
+
+impl Channel {
+    fn new(name: &str, color: u32) -> Channel {
+        Channel {
+            name: name.to_owned(),
+            color: unpack_color(color),
+            line_width: 1.0,
+            visible: true,
+            solo: false,
+            blocks: Arc::new(Mutex::new(Vec::new())),
+            mapped: None,
+            points: Vec::new(),
+            pan: 1.0,
+            scale: 1.0,
+            lane_weight: 1.0,
+            style: DrawStyle::Line,
+            digital: false,
+            summary: Vec::new(),
+        }
+    }
+
+    fn samples(&self) -> Samples {
+        match self.mapped {
+            Some(ref mapped) => Samples::Mapped(mapped),
+            None => Samples::Blocks(self.blocks.lock().unwrap()),
+        }
+    }
+
+    /// Copies the samples from start to end, or fewer if the channel ends
+    /// earlier. Blocks are copied whole so that the lock is held briefly.
+    fn copy_range(&self, start: usize, end: usize) -> Vec<f64> {
+        let blocks = match self.samples() {
+            Samples::Blocks(blocks) => blocks,
+            Samples::Mapped(mapped) => {
+                return (start..min(end, mapped.sample_count())).map(|i| mapped.sample(i)).collect();
+            }
+        };
+
+        let end = min(end, blocks.sample_count());
+        let mut values = Vec::with_capacity(end.saturating_sub(start));
+        let mut i = start;
+
+        while i < end {
+            let from = i % 32;
+            let to = min(32, from + (end - i));
+
+            values.extend_from_slice(&blocks[i / 32].data0[from..to]);
+            i += to - from;
+        }
+
+        values
+    }
+
+    /// Summarizes blocks added since the last call.
+    fn update_summary(&mut self) {
+        let blocks = self.blocks.lock().unwrap();
+
+        if blocks.len() < self.summary.len() * SUMMARY_BLOCKS {
+            self.summary.clear();
+        }
+
+        while (self.summary.len() + 1) * SUMMARY_BLOCKS <= blocks.len() {
+            let start = self.summary.len() * SUMMARY_BLOCKS;
+
+            let range = blocks[start..start + SUMMARY_BLOCKS]
+                .iter()
+                .fold(None, |range, block| merge_range(range, block.min_max(0, 32)));
+
+            self.summary.push(range);
+        }
+    }
+
+    /// Same as Lookup::min_max but uses the summary for whole summarized groups.
+    fn coarse_min_max(&self, blocks: &dyn Lookup, start: f64, end: f64) -> Option<(f64, f64)> {
+        let group = (SUMMARY_BLOCKS * 32) as f64;
+
+        let first = (start.max(0.0) / group).ceil() as usize;
+        let last = min((end.max(0.0) / group).floor() as usize, self.summary.len());
+
+        if first >= last {
+            return blocks.min_max(start, end);
+        }
+
+        let range = self.summary[first..last]
+            .iter()
+            .fold(None, |range, &summary| merge_range(range, summary));
+
+        let range = merge_range(range, blocks.min_max(start, first as f64 * group));
+        merge_range(range, blocks.min_max(last as f64 * group, end))
+    }
+
+    fn packed_color(&self) -> u32 {
+        pack_color(self.color)
+    }
+
+    /// Digital channels are drawn as logic levels by default.
+    fn set_digital(&mut self, digital: bool) {
+        self.digital = digital;
+        self.style = if digital {
+            DrawStyle::Logic
+        } else {
+            DrawStyle::Line
+        };
+    }
+
+    fn value_to_y(&self, value: f64, center_y: f64, y_scale: f64) -> f64 {
+        center_y - y_scale * self.scale as f64 * (value + self.pan as f64)
+    }
+
+    fn y_to_value(&self, y: f64, center_y: f64, y_scale: f64) -> f64 {
+        (center_y - y) / (y_scale * self.scale as f64) - self.pan as f64
+    }
+
+    /// Sets scale and pan so that [min, max] fills the given height. Offset is the
+    /// distance from the center of that height to center_y.
+    fn fit(&mut self, range: (f64, f64), height: f64, y_scale: f64, offset: f64) {
+        let (min, max) = range;
+        let span = if max - min > 1e-9 { max - min } else { 1.0 };
+
+        self.scale = (height * 0.9 / (span * y_scale)) as f32;
+        self.pan = (offset / (y_scale * self.scale as f64) - (min + max) / 2.0) as f32;
+    }
+
+    /// Zooms the y axis by factor while keeping value at the same screen position.
+    fn zoom_y(&mut self, value: f64, factor: f64) {
+        self.pan = ((value + self.pan as f64) / factor - value) as f32;
+        self.scale = (self.scale as f64 * factor) as f32;
+    }
+}
+
+const SUMMARY_BLOCKS: usize = 1024;
+
+const LANE_HANDLE_WIDTH: f64 = 12.0;
+const MIN_LANE_HEIGHT: f64 = 20.0;
+
+/// Smallest distance between time axis ticks in pixels.
+const AXIS_TICK_SPACING: f64 = 100.0;
+
+/// Pixels between samples from which on every sample gets a dot.
+const SAMPLE_DOT_SPACING: f64 = 8.0;
+
+/// Height of a bus trace and width of its crossings, in pixels.
+const BUS_HEIGHT: f32 = 18.0;
+const BUS_TAPER: f32 = 3.0;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Layout {
+    Overlay,
+    Stacked,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum DrawStyle {
+    Line,
+    /// Sample and hold
+    Step,
+    Points,
+    /// Thin step trace that shows every transition, even when zoomed out
+    Logic,
+    /// Band with the value in hex, for multi-bit signals
+    Bus,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Lane {
+    top: f64,
+    height: f64,
+    center_y: f64,
+}
+
+impl Lane {
+    fn contains(&self, y: f64) -> bool {
+        y >= self.top && y < self.top + self.height
+    }
+
+    /// Distance from the middle of the lane to the y axis origin.
+    fn offset(&self) -> f64 {
+        self.center_y - (self.top + self.height / 2.0)
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum LaneDrag {
+    Resize(usize),
+    Move(usize),
+}
+
+/// How sample indices map to time.
+enum TimeBase {
+    Samples,
+
+    /// Samples per second, and the time of the first sample.
+    Rate { rate: f64, start: f64 },
+
+    /// Time of every sample, from a time column in the file.
+    Column(Arc<Mutex<Vec<Box<Block>>>>),
+}
+
+impl TimeBase {
+    /// Whether both map samples to the same times. Time columns never do, as
+    /// they belong to a single file.
+    fn same(&self, other: &TimeBase) -> bool {
+        match (self, other) {
+            (&TimeBase::Samples, &TimeBase::Samples) => true,
+            (&TimeBase::Rate { rate: a, start: b }, &TimeBase::Rate { rate: c, start: d }) => a == c && b == d,
+            _ => false,
+        }
+    }
+
+    /// Time of sample x, in seconds or in the unit of the time column.
+    fn at(&self, x: f64) -> Option<f64> {
+        match *self {
+            TimeBase::Samples => None,
+            TimeBase::Rate { rate, start } => Some(start + x / rate),
+            TimeBase::Column(ref time) => time.lock().unwrap().lookup(x, 1.0),
+        }
+    }
+}
+
 struct Data {
-    blocks_ch0: Arc<Mutex<Vec<Box<Block>>>>,
-    blocks_ch1: Arc<Mutex<Vec<Box<Block>>>>,
-    points_ch0: Vec<ImVec2>,
-    points_ch1: Vec<ImVec2>,
+    channels: Vec<Channel>,
+    time: TimeBase,
+
+    /// Channel properties from a session given on the command line, applied by
+    /// index to the channels of the queued sources.
+    properties: Vec<session::ChannelProperties>,
+
+    /// Set while opening files after the first one given on the command line,
+    /// so that their channels are added to the open ones.
+    add_channels: bool,
 }
 
 impl Data {
     fn new() -> Data {
         Data {
-            blocks_ch0: Arc::new(Mutex::new(Vec::new())),
-            blocks_ch1: Arc::new(Mutex::new(Vec::new())),
-            points_ch0: Vec::new(),
-            points_ch1: Vec::new(),
+            channels: vec![
+                Channel::new("Ch 0", 0xdf00dfff),
+                Channel::new("Ch 1", 0xdf1010ff),
+            ],
+            time: TimeBase::Samples,
+            properties: Vec::new(),
+            add_channels: false,
         }
     }
+
+    /// Replaces the channels and their time base, or adds to them with
+    /// add_channels. Added channels keep the time base only if theirs is the
+    /// same, otherwise time falls back to sample numbers. Returns the block
+    /// lists of the new channels for the loading thread.
+    fn set_channels(&mut self, mut channels: Vec<Channel>, time: TimeBase) -> Vec<Arc<Mutex<Vec<Box<Block>>>>> {
+        let first = if self.add_channels { self.channels.len() } else { 0 };
+
+        for (i, channel) in channels.iter_mut().enumerate() {
+            if self.add_channels {
+                channel.color = unpack_color(channel_color(first + i));
+            }
+
+            if let Some(properties) = self.properties.get(first + i) {
+                properties.apply(channel);
+            }
+        }
+
+        let blocks = channels.iter().map(|channel| channel.blocks.clone()).collect();
+
+        if self.add_channels {
+            self.channels.extend(channels);
+
+            if !self.time.same(&time) {
+                self.time = TimeBase::Samples;
+            }
+        } else {
+            self.channels = channels;
+            self.time = time;
+        }
+
+        blocks
+    }
+
+    /// Whether each channel should be drawn, taking solo into account.
+    fn shown(&self) -> Vec<bool> {
+        let any_solo = self.channels.iter().any(|channel| channel.solo);
+
+        self.channels
+            .iter()
+            .map(|channel| channel.visible && (channel.solo || !any_solo))
+            .collect()
+    }
+}
+
+/// Colors for channels that are created from files.
+const CHANNEL_COLORS: [u32; 6] = [
+    0xdf00dfff, 0xdf1010ff, 0xdfffa030, 0xdf30ff30, 0xdf30c0ff, 0xdfff30a0,
+];
+
+fn channel_color(index: usize) -> u32 {
+    CHANNEL_COLORS[index % CHANNEL_COLORS.len()]
+}
+
+/// ImGui colors are packed as 0xAABBGGRR.
+fn pack_color(color: [f32; 4]) -> u32 {
+    color
+        .iter()
+        .enumerate()
+        .fold(0, |packed, (i, c)| packed | ((clamp(0.0, *c, 1.0) * 255.0) as u32) << (8 * i))
+}
+
+fn unpack_color(color: u32) -> [f32; 4] {
+    let mut unpacked = [0.0; 4];
+
+    for (i, c) in unpacked.iter_mut().enumerate() {
+        *c = ((color >> (8 * i)) & 0xff) as f32 / 255.0;
+    }
+
+    unpacked
 }
 
 impl fmt::Debug for Data {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "Data {{ blocks_ch0: {}, blocks_ch1: {}, points_ch0: {}, points_ch1: {} }}",
-            self.blocks_ch0.lock().unwrap().len(),
-            self.blocks_ch1.lock().unwrap().len(),
-            self.points_ch0.len(),
-            self.points_ch1.len()
-        )
+        write!(f, "Data {{ ")?;
+        for channel in &self.channels {
+            write!(
+                f,
+                "{}: {{ blocks: {}, points: {} }} ",
+                channel.name,
+                channel.blocks.lock().unwrap().len(),
+                channel.points.len()
+            )?;
+        }
+        write!(f, "}}")
+    }
+}
+
+/// How much of the current file has been loaded and how long that took.
+#[derive(Debug, Copy, Clone)]
+struct LoadReport {
+    bytes: u64,
+
+    /// Size of the file, which for compressed files is the compressed size.
+    total: u64,
+
+    seconds: f64,
+}
+
+impl LoadReport {
+    /// Sets the time on the last report of a load. A load that ran to the end
+    /// has read the whole file, one that was cancelled or failed keeps how far
+    /// it got.
+    fn finish(report: &Mutex<Option<LoadReport>>, seconds: f64, complete: bool) {
+        if let Some(ref mut report) = *report.lock().unwrap() {
+            report.seconds = seconds;
+
+            if complete {
+                report.bytes = report.total;
+            }
+        }
+    }
+
+    /// Seconds left at the rate loading has had so far.
+    fn remaining(&self) -> Option<f64> {
+        if self.bytes > 0 && self.bytes < self.total {
+            Some(self.seconds * (self.total - self.bytes) as f64 / self.bytes as f64)
+        } else {
+            None
+        }
+    }
+}
+
+/// Reports the position in the file read through it as load progress.
+struct ProgressReader<R> {
+    inner: R,
+    report: Arc<Mutex<Option<LoadReport>>>,
+    bytes: u64,
+    total: u64,
+    timer: timer::Timer,
+}
+
+impl ProgressReader<File> {
+    fn open(path: &str, report: &Arc<Mutex<Option<LoadReport>>>) -> io::Result<ProgressReader<File>> {
+        let file = File::open(path)?;
+        let total = file.metadata()?.len();
+
+        Ok(ProgressReader::new(file, total, report))
+    }
+}
+
+impl<R> ProgressReader<R> {
+    fn new(inner: R, total: u64, report: &Arc<Mutex<Option<LoadReport>>>) -> ProgressReader<R> {
+        ProgressReader {
+            inner: inner,
+            report: report.clone(),
+            bytes: 0,
+            total: total,
+            timer: timer::Timer::new(),
+        }
+    }
+
+    fn update(&self) {
+        *self.report.lock().unwrap() = Some(LoadReport {
+            bytes: self.bytes.min(self.total),
+            total: self.total,
+            seconds: self.timer.elapsed(),
+        });
+    }
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buffer)?;
+        self.bytes += read as u64;
+        self.update();
+        Ok(read)
+    }
+}
+
+impl<R: Seek> Seek for ProgressReader<R> {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        self.bytes = self.inner.seek(position)?;
+        self.update();
+        Ok(self.bytes)
     }
 }
 
@@ -118,13 +719,26 @@ struct State {
     loading: Arc<AtomicBool>,
     stop_loading: Arc<AtomicBool>,
     loading_thread: Option<thread::JoinHandle<()>>,
+    load_report: Arc<Mutex<Option<LoadReport>>>,
+
+    /// Set by Close. The channels are cleared once loading has stopped.
+    clear_when_stopped: bool,
+    notifications: Arc<Mutex<notify::Notifications>>,
 
     data: Data,
 
     pan: (f64, f64),
     panning: bool,
 
+    layout: Layout,
+    lane_drag: Option<LaneDrag>,
+
+    show_overview: bool,
+    overview_drag: Option<overview::Drag>,
+
     frame_timer: timer::Timer,
+    redraw_frames: u32,
+    waker: Waker,
 
     mouse_state: MouseState,
     last_mouse_state: MouseState,
@@ -135,12 +749,29 @@ struct State {
 
     window_y_scale: f32,
 
-    ch0_pan: f32,
-    ch0_scale: f32,
     ch0_smooth: Arc<Mutex<f32>>,
-    
-    ch1_pan: f32,
-    ch1_scale: f32,
+
+    auto_fit: bool,
+    lock_y_scale: bool,
+
+    follow: bool,
+    follow_window: f32,
+
+    /// Keep reading lines appended to opened text files.
+    follow_file: bool,
+
+    /// Sources given on the command line, opened one after the other.
+    open_queue: VecDeque<Source>,
+
+    /// Sample range to show once the window size is known.
+    initial_view: Option<(f64, f64)>,
+
+    scope: scope::Scope,
+    csv_preview: Option<csv::Preview>,
+    binary_import: Option<binary::Import>,
+    wav_export: Option<export::Export<wav::Options>>,
+    npy_export: Option<export::Export<npy::Options>>,
+    density: density::Density,
 
     rise_value: Arc<Mutex<f32>>,
 }
@@ -151,245 +782,1175 @@ impl State {
             loading: Arc::new(AtomicBool::new(false)),
             stop_loading: Arc::new(AtomicBool::new(false)),
             loading_thread: None,
+            load_report: Arc::new(Mutex::new(None)),
+            clear_when_stopped: false,
+            notifications: Arc::new(Mutex::new(notify::Notifications::new())),
             data: Data::new(),
             pan: (0.0, 0.0),
             panning: false,
+            layout: Layout::Overlay,
+            lane_drag: None,
+            show_overview: true,
+            overview_drag: None,
             frame_timer: timer::Timer::new(),
+            redraw_frames: REDRAW_FRAMES,
+            waker: Waker::new(None),
             mouse_state: MouseState::new(),
             last_mouse_state: MouseState::new(),
             quit: false,
             scroll_factor: 0.0,
             window_y_scale: 1.0,
-            ch0_pan: 1.0,
-            ch0_scale: 1.0,
             ch0_smooth: Arc::new(Mutex::new(0.0)),
-            ch1_pan: 1.0,
-            ch1_scale: 1.0,
+            auto_fit: false,
+            lock_y_scale: false,
+            follow: false,
+            follow_window: 1000.0,
+            follow_file: false,
+            open_queue: VecDeque::new(),
+            initial_view: None,
+            scope: scope::Scope::new(),
+            csv_preview: None,
+            binary_import: None,
+            wav_export: None,
+            npy_export: None,
+            density: density::Density::new(),
             rise_value: Arc::new(Mutex::new(0.0)),
         }
     }
 }
 
-fn open_file(path: &str, state: &mut State) {
-    if !state
+/// Something to open from the command line.
+#[derive(Debug, Clone)]
+enum Source {
+    File(String),
+    Stdin,
+    Port(PortSettings),
+}
+
+/// Opens a file by its type. Tables are shown in the import preview when
+/// preview is set, otherwise they are loaded with the detected format.
+fn open_path(path: &str, preview: bool, state: &mut State) {
+    let compressed = text::compression(path).ok().map_or(false, |c| c.is_some());
+
+    if compressed {
+        open_file(path, state);
+    } else if wav::is_wav(path) {
+        open_wav(path, state);
+    } else if path.to_lowercase().ends_with(".vcd") {
+        open_vcd(path, state);
+    } else if sigrok::is_session(path) {
+        open_sigrok(path, state);
+    } else if npy::is_npy(path) {
+        open_npy(path, state);
+    } else if let Ok(Some(capture)) = instrument::detect(path) {
+        open_capture(path, &capture, state);
+    } else {
+        match csv::Preview::read(path) {
+            Ok(ref table) if !table.is_table() => open_file(path, state),
+            Ok(ref table) if !preview => open_csv(path, &table.format, None, state),
+            Ok(table) => state.csv_preview = Some(table),
+            Err(e) => state.notifications.lock().unwrap().error(path, e),
+        }
+    }
+}
+
+/// Opens the next queued source once the previous one has finished loading.
+/// Each source after the first adds its channels to the open ones, and only
+/// the last one can follow its file, as a followed file never finishes.
+fn open_queued(state: &mut State) {
+    if state.loading.load(Ordering::SeqCst) || state.clear_when_stopped {
+        return;
+    }
+
+    let source = match state.open_queue.pop_front() {
+        Some(source) => source,
+        None => return,
+    };
+
+    let follow_file = state.follow_file;
+    state.follow_file = follow_file && state.open_queue.is_empty();
+
+    match source {
+        Source::File(path) => open_path(&path, false, state),
+        Source::Stdin => open_stream("stdin", || Ok(io::stdin()), state),
+        Source::Port(settings) => open_com_port(&settings, state),
+    }
+
+    state.follow_file = follow_file;
+    state.data.add_channels = !state.open_queue.is_empty();
+
+    if state.open_queue.is_empty() {
+        state.data.properties.clear();
+    }
+}
+
+/// What the loader of a file gets on the loading thread: the blocks of the
+/// channels, in the order they were given, and what it needs to stop and to
+/// report its progress.
+struct Loader {
+    blocks: Vec<Arc<Mutex<Vec<Box<Block>>>>>,
+    stop_loading: Arc<AtomicBool>,
+    waker: Waker,
+    report: Arc<Mutex<Option<LoadReport>>>,
+}
+
+/// Sets the channels and runs load on the loading thread, unless something is
+/// loading already. Skipped lines and errors are shown as notifications from
+/// name. Returns whether loading started.
+fn start_loading<F, E>(name: &str, channels: Vec<Channel>, time: TimeBase, state: &mut State, load: F) -> bool
+where
+    F: FnOnce(Loader) -> Result<notify::BadLines, E> + Send + 'static,
+    E: fmt::Display,
+{
+    if state
         .loading
         .compare_and_swap(false, true, Ordering::SeqCst)
     {
-        state.stop_loading.store(false, Ordering::SeqCst);
+        return false;
+    }
+
+    state.stop_loading.store(false, Ordering::SeqCst);
+
+    let loader = Loader {
+        blocks: state.data.set_channels(channels, time),
+        stop_loading: state.stop_loading.clone(),
+        waker: state.waker.clone(),
+        report: state.load_report.clone(),
+    };
+    *state.load_report.lock().unwrap() = None;
+
+    let loading = state.loading.clone();
+    let stop_loading = state.stop_loading.clone();
+    let waker = state.waker.clone();
+    let report = state.load_report.clone();
+    let notifications = state.notifications.clone();
+    let owned_name = name.to_owned();
+
+    state.loading_thread = Some(thread::spawn(move || {
+        let mut t = timer::Timer::new();
+
+        let complete = match load(loader) {
+            Ok(bad_lines) => {
+                notifications.lock().unwrap().bad_lines(&owned_name, &bad_lines);
+                !stop_loading.load(Ordering::SeqCst)
+            }
+            Err(e) => {
+                notifications.lock().unwrap().error(&owned_name, e);
+                false
+            }
+        };
 
-        state.data.blocks_ch0.lock().unwrap().clear();
+        LoadReport::finish(&report, t.reset(), complete);
 
-        let blocks = state.data.blocks_ch0.clone();
-        let loading = state.loading.clone();
-        let stop_loading = state.stop_loading.clone();
+        loading.store(false, Ordering::SeqCst);
+        waker.wake_now();
+    }));
+
+    true
+}
+
+fn open_file(path: &str, state: &mut State) {
+    if text::is_fifo(path) {
+        // Opening a pipe waits for a writer, so it is done by the loading thread
         let owned_path = path.to_owned();
+        open_stream(path, move || File::open(owned_path), state);
+        return;
+    }
+
+    let follow = state.follow_file;
+    let owned_path = path.to_owned();
+    let notifications = state.notifications.clone();
+    let channels = vec![Channel::new("Ch 0", 0xdf00dfff)];
+
+    let started = start_loading(path, channels, TimeBase::Samples, state, move |loader| {
+        match text::compression(&owned_path)? {
+            Some(compression) => text::load_compressed(
+                &owned_path,
+                compression,
+                &loader.blocks[0],
+                &loader.stop_loading,
+                &loader.waker,
+                &loader.report,
+            ),
+            None => text::load(
+                &owned_path,
+                follow,
+                &loader.blocks[0],
+                &loader.stop_loading,
+                &loader.waker,
+                &loader.report,
+                &notifications,
+            ),
+        }
+    });
+
+    if started && follow {
+        state.follow = true;
+    }
+}
+
+/// Plots lines from a pipe as they arrive, like the serial port. The reader
+/// is opened by the loading thread.
+fn open_stream<R, F>(name: &str, open: F, state: &mut State)
+where
+    R: Read + Send + 'static,
+    F: FnOnce() -> io::Result<R> + Send + 'static,
+{
+    let channels = vec![Channel::new("Ch 0", 0xdf00dfff)];
+
+    let started = start_loading(name, channels, TimeBase::Samples, state, move |loader| {
+        text::stream(open()?, &loader.blocks[0], &loader.stop_loading, &loader.waker)
+    });
+
+    if started {
+        state.follow = true;
+    }
+}
+
+fn open_wav(path: &str, state: &mut State) {
+    let spec = match wav::spec(path) {
+        Ok(spec) => spec,
+        Err(e) => {
+            state.notifications.lock().unwrap().error(path, e);
+            return;
+        }
+    };
+
+    let channels = (0..spec.channels as usize)
+        .map(|i| Channel::new(&format!("Ch {}", i), channel_color(i)))
+        .collect();
+    let time = TimeBase::Rate {
+        rate: spec.sample_rate as f64,
+        start: 0.0,
+    };
+    let owned_path = path.to_owned();
+
+    start_loading(path, channels, time, state, move |loader| {
+        wav::load(&owned_path, &loader.blocks, &loader.stop_loading, &loader.waker, &loader.report)
+            .map(|_| notify::BadLines::new())
+    });
+}
+
+/// Loads a table. The time base comes from the time column, unless one is
+/// given.
+fn open_csv(path: &str, format: &csv::Format, time: Option<TimeBase>, state: &mut State) {
+    if format.value_columns().is_empty() {
+        state.notifications.lock().unwrap().error(path, "no columns with values");
+        return;
+    }
 
-        state.loading_thread = Some(thread::spawn(move || {
-            let mut t = timer::Timer::new();
+    let channels = format
+        .value_columns()
+        .iter()
+        .enumerate()
+        .map(|(i, column)| Channel::new(&column.name, channel_color(i)))
+        .collect();
+    let time_column = if format.has_time() {
+        Some(Arc::new(Mutex::new(Vec::new())))
+    } else {
+        None
+    };
+    let time = time.unwrap_or_else(|| time_column.clone().map_or(TimeBase::Samples, TimeBase::Column));
+    let owned_path = path.to_owned();
+    let format = format.clone();
+
+    start_loading(path, channels, time, state, move |loader| {
+        csv::load(
+            &owned_path,
+            &format,
+            &loader.blocks,
+            time_column.as_ref(),
+            &loader.stop_loading,
+            &loader.waker,
+            &loader.report,
+        )
+    });
+}
 
-            if let Ok(file) = File::open(&owned_path) {
-                let reader = BufReader::new(&file);
+/// Every column of every array becomes a channel.
+fn open_npy(path: &str, state: &mut State) {
+    let arrays = match npy::arrays(path) {
+        Ok(arrays) => arrays,
+        Err(e) => {
+            state.notifications.lock().unwrap().error(path, e);
+            return;
+        }
+    };
 
-                let mut block = Box::new(Block::new());
+    let channels = arrays
+        .iter()
+        .flat_map(|array| array.channel_names())
+        .enumerate()
+        .map(|(i, name)| Channel::new(&name, channel_color(i)))
+        .collect();
+    let owned_path = path.to_owned();
+
+    start_loading(path, channels, TimeBase::Samples, state, move |loader| {
+        npy::load(&owned_path, &arrays, &loader.blocks, &loader.stop_loading, &loader.waker, &loader.report)
+            .map(|_| notify::BadLines::new())
+    });
+}
 
-                for maybe_line in reader.lines() {
-                    if stop_loading.load(Ordering::SeqCst) {
-                        break;
+fn open_sigrok(path: &str, state: &mut State) {
+    let session = match sigrok::read_session(path) {
+        Ok(session) => session,
+        Err(e) => {
+            state.notifications.lock().unwrap().error(path, e);
+            return;
+        }
+    };
+
+    let channels = session
+        .probes
+        .iter()
+        .enumerate()
+        .map(|(i, probe)| {
+            let mut channel = Channel::new(&probe.name, channel_color(i));
+            channel.set_digital(!probe.analog);
+            channel
+        })
+        .collect();
+    let time = match session.sample_rate {
+        Some(rate) => TimeBase::Rate { rate: rate, start: 0.0 },
+        None => TimeBase::Samples,
+    };
+    let owned_path = path.to_owned();
+
+    start_loading(path, channels, time, state, move |loader| {
+        sigrok::load(&owned_path, &session, &loader.blocks, &loader.stop_loading, &loader.waker, &loader.report)
+            .map(|_| notify::BadLines::new())
+    });
+}
+
+/// Scalar wires become logic channels and vectors step traces of their value.
+fn open_vcd(path: &str, state: &mut State) {
+    let header = match vcd::read_header(path) {
+        Ok(header) => header,
+        Err(e) => {
+            state.notifications.lock().unwrap().error(path, e);
+            return;
+        }
+    };
+
+    let channels = header
+        .vars
+        .iter()
+        .enumerate()
+        .map(|(i, var)| {
+            let mut channel = Channel::new(&var.name, channel_color(i));
+
+            if var.width == 1 {
+                channel.set_digital(true);
+            } else {
+                channel.style = DrawStyle::Bus;
+            }
+
+            channel
+        })
+        .collect();
+    let time = TimeBase::Rate {
+        rate: header.rate(),
+        start: header.start(),
+    };
+    let owned_path = path.to_owned();
+    let notifications = state.notifications.clone();
+
+    start_loading(path, channels, time, state, move |loader| {
+        vcd::load(&owned_path, &header, &loader.blocks, &loader.stop_loading, &loader.waker, &loader.report)
+            .map(|off_grid| {
+                if off_grid > 0 {
+                    let message = format!(
+                        "{} timestamps fall between samples, their changes show at the next sample",
+                        off_grid
+                    );
+                    notifications.lock().unwrap().warning(&owned_path, &message);
+                }
+
+                notify::BadLines::new()
+            })
+    });
+}
+
+/// Loads an oscilloscope export with the time base from its preamble.
+fn open_capture(path: &str, capture: &instrument::Capture, state: &mut State) {
+    let time = if capture.interval > 0.0 {
+        Some(TimeBase::Rate {
+            rate: 1.0 / capture.interval,
+            start: capture.start,
+        })
+    } else {
+        None
+    };
+
+    open_csv(path, &capture.format, time, state);
+}
+
+fn open_binary(path: &str, format: &binary::Format, state: &mut State) {
+    let channels = (0..format.channels)
+        .map(|i| Channel::new(&format!("Ch {}", i), channel_color(i)))
+        .collect();
+    let owned_path = path.to_owned();
+    let format = format.clone();
+
+    start_loading(path, channels, TimeBase::Samples, state, move |loader| {
+        binary::load(&owned_path, &format, &loader.blocks, &loader.stop_loading, &loader.waker, &loader.report)
+            .map(|_| notify::BadLines::new())
+    });
+}
+
+fn open_mapped(path: &str, format: &binary::Format, state: &mut State) {
+    let mapped = match mapped::open(path, format, &state.notifications) {
+        Ok(mapped) => mapped,
+        Err(e) => {
+            state.notifications.lock().unwrap().error(path, e);
+            return;
+        }
+    };
+
+    let channels = mapped
+        .iter()
+        .enumerate()
+        .map(|(i, mapped)| {
+            let mut channel = Channel::new(&format!("Ch {}", i), channel_color(i));
+            channel.mapped = Some(mapped.clone());
+            channel
+        })
+        .collect();
+    let owned_path = path.to_owned();
+
+    start_loading(path, channels, TimeBase::Samples, state, move |loader| {
+        mapped::index(&owned_path, &mapped, &loader.stop_loading, &loader.waker, &loader.report)
+            .map(|_| notify::BadLines::new())
+    });
+}
+
+/// How samples arrive over the serial port.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Decoder {
+    /// Two byte frames with a sync bit, a light sensor value and a light state,
+    /// used for latency measurements.
+    Latency,
+
+    /// One value per line of text, like a text file.
+    Lines,
+}
+
+#[derive(Debug, Clone)]
+struct PortSettings {
+    /// Port to open. Without a name the first of COM1 to COM30 that opens is used.
+    name: Option<String>,
+    baud_rate: u32,
+    decoder: Decoder,
+}
+
+impl PortSettings {
+    fn new() -> PortSettings {
+        PortSettings {
+            name: None,
+            baud_rate: 250000,
+            decoder: Decoder::Latency,
+        }
+    }
+
+    fn open(&self) -> Result<(String, Box<dyn serialport::SerialPort>), String> {
+        let s = serialport::SerialPortSettings {
+            baud_rate: self.baud_rate,
+            data_bits: serialport::DataBits::Eight,
+            flow_control: serialport::FlowControl::None,
+            parity: serialport::Parity::None,
+            stop_bits: serialport::StopBits::One,
+            timeout: Duration::from_millis(1),
+        };
+
+        if let Some(ref name) = self.name {
+            return serialport::open_with_settings(name, &s)
+                .map(|port| (name.clone(), port))
+                .map_err(|e| e.description);
+        }
+
+        (1..=30)
+            .map(|i| format!("COM{}", i))
+            .filter_map(|name| serialport::open_with_settings(&name, &s).ok().map(|port| (name, port)))
+            .next()
+            .ok_or_else(|| "no port found on COM1 to COM30".to_owned())
+    }
+}
+
+fn open_com_port(settings: &PortSettings, state: &mut State) {
+    if settings.decoder == Decoder::Lines {
+        let name = settings.name.clone().unwrap_or_else(|| "Serial port".to_owned());
+        let settings = settings.clone();
+
+        // Scanning for a port takes a while, so the loading thread does it
+        open_stream(
+            &name,
+            move || {
+                settings
+                    .open()
+                    .map(|(name, port)| {
+                        println!("Using {}", name);
+                        port
+                    })
+                    .map_err(|e| io::Error::new(ErrorKind::Other, e))
+            },
+            state,
+        );
+        return;
+    }
+
+    let mut channels = vec![
+        Channel::new("Ch 0", 0xdf00dfff),
+        Channel::new("Ch 1", 0xdf1010ff),
+    ];
+    channels[1].set_digital(true);
+
+    let ch0_smooth = state.ch0_smooth.clone();
+    let rise_value = state.rise_value.clone();
+    let settings = settings.clone();
+
+    let started = start_loading("Serial port", channels, TimeBase::Samples, state, move |loader| {
+        let (name, mut sp) = settings.open()?;
+        println!("Using {}", name);
+
+        let mut buffer = Vec::new();
+
+        let mut block_ch0 = Box::new(Block::new());
+        let mut block_ch1 = Box::new(Block::new());
+
+        let mut ch0_avg = 0.0;
+
+        let mut start_timestamp = 0;
+        let mut last_ligh_on = 0;
+        let mut measuring_cycle = false;
+
+        while !loader.stop_loading.load(Ordering::SeqCst) {
+            
+            {
+                let mut receive_buffer = [0; 6];
+
+                match sp.read(&mut receive_buffer) {
+                    Ok(amt) => {
+                        buffer.extend_from_slice(&receive_buffer[..amt]);
                     }
+                    Err(ref e) if e.kind() == ErrorKind::TimedOut => (),
+                    Err(e) => println!("{:?}", e),
+                }
+            }
 
-                    if let Ok(line) = maybe_line {
-                        if let Ok(val) = line.parse::<f64>() {
-                            if block.data0.is_full() {
-                                blocks.lock().unwrap().push(block);
-                                block = Box::new(Block::new());
-                            }
+            if buffer.len() > 6 {
+
+                let value = LittleEndian::read_u16(&buffer[..2]);
+                
+                let sync = (value >> 15) & 0b1;
+
+                if sync != 0b1 {
+                    println!("OUT OF SYNC");
+                    buffer.remove(0);
+                } else {
+
+                    let light_on = (value >> 14) & 0b1;
+
+                    let high = (value >> 8) & 0b00011111;
+                    let low = value & 0b00011111;
+
+                    let analog = (high << 5) | low;
+
+                    let analog_flipped = 1024.0 - analog as f64;
+
+                    let ch0_smooth_value = *ch0_smooth.lock().unwrap() as f64;
+
+                    ch0_avg = ch0_avg*ch0_smooth_value + analog_flipped*(1.0 - ch0_smooth_value);
 
-                            block.push(val);
+                    let time = {
+                        let time_packed = LittleEndian::read_u32(&buffer[2..6]);
+
+                        // The high bit in every byte of time_packed is 0 becouse of the sync bit 
+                        // in the high byte of value above. So we need to unpack this into a proper u32.
+                        // The bottom 4 bit of the u32 is discarded on the arduino to make room.
+
+                        let time = 
+                            ( ((time_packed >> 3) & 0x0fe0_0000)
+                            | ((time_packed >> 2) & 0x001f_c000)
+                            | ((time_packed >> 1) & 0x0000_3f80)
+                            | ((time_packed)      & 0x0000_007f)) << 4;
+
+                        time
+                    };
+
+                    {
+                        if last_ligh_on == 1 && light_on == 0 {
+                            start_timestamp = time;
+                            measuring_cycle = true;
+                        }
+
+                        if measuring_cycle && light_on == 1 {
+                            measuring_cycle = false;
                         }
+
+                        if measuring_cycle && ch0_avg < *rise_value.lock().unwrap() as f64 {
+                            measuring_cycle = false;
+                            let latency = time - start_timestamp;
+                            println!("{}", latency as f64 / 1000.0);
+                        }
+
+                        last_ligh_on = light_on;
                     }
-                }
 
-                if block.data0.len() != 0 {
-                    blocks.lock().unwrap().push(block);
+                    {
+                        if block_ch0.data0.is_full() {
+                            loader.blocks[0].lock().unwrap().push(block_ch0);
+                            block_ch0 = Box::new(Block::new());
+                            loader.waker.wake();
+                        }
+
+                        if block_ch1.data0.is_full() {
+                            loader.blocks[1].lock().unwrap().push(block_ch1);
+                            block_ch1 = Box::new(Block::new());
+                        }
+
+                        block_ch0.push(ch0_avg);
+                        block_ch1.push(if light_on == 1 { 100.0 } else { 0.0 });
+                    }
+
+                    buffer.remove(0);
+                    buffer.remove(0);
+                    buffer.remove(0);
+                    buffer.remove(0);
+                    buffer.remove(0);
+                    buffer.remove(0);
                 }
             }
+        }
 
-            println!("Load time: {}", t.reset());
+        Ok::<_, String>(notify::BadLines::new())
+    });
 
-            loading.store(false, Ordering::SeqCst);
-        }));
+    if started {
+        state.follow = true;
     }
 }
 
-fn open_com_port(state: &mut State) {
-    if !state
-        .loading
-        .compare_and_swap(false, true, Ordering::SeqCst)
-    {
-        state.stop_loading.store(false, Ordering::SeqCst);
-
-        state.data.blocks_ch0.lock().unwrap().clear();
-        state.data.blocks_ch1.lock().unwrap().clear();
-
-        let blocks_ch0 = state.data.blocks_ch0.clone();
-        let blocks_ch1 = state.data.blocks_ch1.clone();
-        let loading = state.loading.clone();
-        let stop_loading = state.stop_loading.clone();
-
-        let ch0_smooth = state.ch0_smooth.clone();
-        let rise_value = state.rise_value.clone();
-
-        state.loading_thread = Some(thread::spawn(move || {
-            let s = serialport::SerialPortSettings {
-                baud_rate: 250000,
-                data_bits: serialport::DataBits::Eight,
-                flow_control: serialport::FlowControl::None,
-                parity: serialport::Parity::None,
-                stop_bits: serialport::StopBits::One,
-                timeout: Duration::from_millis(1),
-            };
+/// Points with a NaN y split the trace, so missing data shows up as a gap.
+fn draw_trace(
+    draw_list: &WindowDrawList,
+    points: &[ImVec2],
+    style: DrawStyle,
+    color: u32,
+    width: f32,
+    dots: bool,
+) {
+    let segments = points
+        .iter()
+        .zip(points.iter().skip(1))
+        .filter(|&(p1, p2)| !p1.y.is_nan() && !p2.y.is_nan());
+
+    match style {
+        DrawStyle::Line => {
+            for (p1, p2) in segments {
+                draw_list
+                    .add_line((p1.x, p1.y), (p2.x, p2.y), color)
+                    .thickness(width)
+                    .build();
+            }
+        }
+        DrawStyle::Step | DrawStyle::Logic => {
+            for (p1, p2) in segments {
+                if p1.x != p2.x {
+                    draw_list
+                        .add_line((p1.x, p1.y), (p2.x, p1.y), color)
+                        .thickness(width)
+                        .build();
+                }
+                if p1.y != p2.y {
+                    draw_list
+                        .add_line((p2.x, p1.y), (p2.x, p2.y), color)
+                        .thickness(width)
+                        .build();
+                }
+            }
+        }
+        DrawStyle::Points => {
+            for p in points.iter().filter(|p| !p.y.is_nan()) {
+                draw_list
+                    .add_rect((p.x - width, p.y - width), (p.x + width, p.y + width), color)
+                    .filled(true)
+                    .build();
+            }
+        }
+        // Drawn from the samples by draw_bus
+        DrawStyle::Bus => (),
+    }
 
-            let mut sp = {
-                let mut sp = None;
+    if dots && style != DrawStyle::Points {
+        for p in points.iter().filter(|p| !p.y.is_nan()) {
+            draw_list
+                .add_circle((p.x, p.y), width + 1.5, color)
+                .filled(true)
+                .build();
+        }
+    }
+}
 
-                for i in 1..=30 {
-                    let port = format!("COM{}", i);
+/// What a run of columns of a bus shows.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum BusRun {
+    Value(f64),
+    /// Several values within a column
+    Busy,
+    Gap,
+}
 
-                    match serialport::open_with_settings(&port, &s) {
-                        Ok(s) => {
-                            println!("Using {}", port);
-                            sp = Some(s);
-                            break;
-                        }
-                        Err(_) => {}
-                    }
+/// Draws a band across the lane that crosses over where the value changes,
+/// with the value in hex where there is room for it.
+fn draw_bus(
+    ui: &Ui,
+    draw_list: &WindowDrawList,
+    channel: &Channel,
+    lane: &Lane,
+    origin: f64,
+    scale: f64,
+    view_width: f32,
+) {
+    let samples = channel.samples();
+    let color = channel.packed_color();
+    let center = lane.center_y as f32;
+    let half = (lane.height as f32 * 0.3).min(BUS_HEIGHT / 2.0);
+
+    let run_at = |x: i32| {
+        let start = origin + scale * x as f64;
+
+        match channel.coarse_min_max(&*samples, start, start + scale) {
+            Some((low, high)) if low == high => BusRun::Value(low),
+            Some(_) => BusRun::Busy,
+            None => BusRun::Gap,
+        }
+    };
+
+    let mut start = 0;
+    let mut run = run_at(0);
+
+    for x in 1..=(view_width as i32) {
+        let next = if x < view_width as i32 { run_at(x) } else { BusRun::Gap };
+
+        if next == run {
+            continue;
+        }
+
+        let (x1, x2) = (start as f32, x as f32);
+
+        match run {
+            BusRun::Value(value) => {
+                let taper = ((x2 - x1) / 2.0).min(BUS_TAPER);
+
+                for &y in &[center - half, center + half] {
+                    draw_list.add_line((x1, center), (x1 + taper, y), color).build();
+                    draw_list.add_line((x1 + taper, y), (x2 - taper, y), color).build();
+                    draw_list.add_line((x2 - taper, y), (x2, center), color).build();
                 }
 
-                if let Some(s) = sp {
-                    s
+                let label = if value >= 0.0 && value.fract() == 0.0 && value < 1.8e19 {
+                    format!("{:X}", value as u64)
                 } else {
-                    loading.store(false, Ordering::SeqCst);
-                    return;
+                    format!("{}", value)
+                };
+                let label = ImString::new(label);
+                let size = ui.calc_text_size(&label, false, -1.0);
+
+                if size.x + 2.0 * BUS_TAPER < x2 - x1 {
+                    ui.set_cursor_screen_pos(((x1 + x2 - size.x) / 2.0, center - size.y / 2.0));
+                    ui.text(&label);
                 }
-            };
+            }
+            BusRun::Busy => {
+                draw_list
+                    .add_rect((x1, center - half), (x2, center + half), color)
+                    .filled(true)
+                    .build();
+            }
+            BusRun::Gap => (),
+        }
+
+        start = x;
+        run = next;
+    }
+}
 
-            let mut buffer = Vec::new();
+/// Marks a gap in the trace, unless the trace already ends in one.
+fn push_gap(points: &mut Vec<ImVec2>, x: f32) {
+    if points.last().map_or(false, |p| !p.y.is_nan()) {
+        points.push(ImVec2::new(x, f32::NAN));
+    }
+}
 
-            let mut block_ch0 = Box::new(Block::new());
-            let mut block_ch1 = Box::new(Block::new());
+/// Sample index at the left edge of the view.
+fn x_origin(state: &State, view_width: f64, scale: f64) -> f64 {
+    if state.scope.enabled {
+        if let Some(origin) = state.scope.origin(scale * view_width) {
+            return origin;
+        }
+    }
+
+    scale * (state.pan.0 - view_width / 2.0)
+}
 
-            let mut ch0_avg = 0.0;
+/// 1, 2 or 5 times a power of ten, at least x.
+fn nice_step(x: f64) -> f64 {
+    let magnitude = 10f64.powf(x.log10().floor());
+    let ratio = x / magnitude;
+
+    magnitude * if ratio <= 1.0 {
+        1.0
+    } else if ratio <= 2.0 {
+        2.0
+    } else if ratio <= 5.0 {
+        5.0
+    } else {
+        10.0
+    }
+}
 
-            let mut start_timestamp = 0;
-            let mut last_ligh_on = 0;
-            let mut measuring_cycle = false;
+/// Shows how far loading has come, with a Cancel button that stops loading
+/// and keeps what has been loaded so far.
+fn progress_window(ui: &Ui, state: &State, view_size: (f32, f32)) {
+    let report = *state.load_report.lock().unwrap();
+    let samples = sample_count(&state.data.channels);
+    let stopping = state.stop_loading.load(Ordering::SeqCst);
 
-            while !stop_loading.load(Ordering::SeqCst) {
-                
-                {
-                    let mut receive_buffer = [0; 6];
+    ui.window(im_str!("Loading"))
+        .size((360.0, 0.0), ImGuiCond::FirstUseEver)
+        .position((view_size.0 - 370.0, 30.0), ImGuiCond::FirstUseEver)
+        .collapsible(false)
+        .always_auto_resize(true)
+        .build(|| {
+            match report {
+                Some(report) if report.total > 0 => {
+                    let overlay = ImString::new(format!(
+                        "{:.1} of {:.1} MB",
+                        report.bytes as f64 / 1e6,
+                        report.total as f64 / 1e6
+                    ));
+
+                    ui.progress_bar(report.bytes as f32 / report.total as f32)
+                        .overlay_text(&overlay)
+                        .size((340.0, 0.0))
+                        .build();
 
-                    match sp.read(&mut receive_buffer) {
-                        Ok(amt) => {
-                            buffer.extend_from_slice(&receive_buffer[..amt]);
-                        }
-                        Err(ref e) if e.kind() == ErrorKind::TimedOut => (),
-                        Err(e) => println!("{:?}", e),
+                    match report.remaining() {
+                        Some(seconds) => ui.text(format!("{} samples, {:.0} s left", samples, seconds.ceil())),
+                        None => ui.text(format!("{} samples", samples)),
                     }
                 }
+                _ => ui.text(format!("{} samples", samples)),
+            }
 
-                if buffer.len() > 6 {
+            if stopping {
+                ui.text("Stopping...");
+            } else if ui.button(im_str!("Cancel"), (0.0, 0.0)) {
+                state.stop_loading.store(true, Ordering::SeqCst);
+            }
+        });
+}
 
-                    let value = LittleEndian::read_u16(&buffer[..2]);
-                    
-                    let sync = (value >> 15) & 0b1;
+/// Shows a file dialog. Failures of the dialog itself are reported.
+fn file_dialog(notifications: &Mutex<notify::Notifications>, filter: &str, save: bool) -> Option<String> {
+    let response = if save {
+        nfd::open_save_dialog(Some(filter), None)
+    } else {
+        nfd::open_file_dialog(Some(filter), None)
+    };
 
-                    if sync != 0b1 {
-                        println!("OUT OF SYNC");
-                        buffer.remove(0);
-                    } else {
+    match response {
+        Ok(nfd::Response::Okay(path)) => Some(path),
+        Ok(_) => None,
+        Err(e) => {
+            notifications.lock().unwrap().error("File dialog", e);
+            None
+        }
+    }
+}
 
-                        let light_on = (value >> 14) & 0b1;
+/// Draws ticks along the bottom of the plot. With a sample rate they are placed
+/// at round times, otherwise at round sample indices labeled with the sample
+/// index or the time column.
+fn draw_time_axis(
+    ui: &Ui,
+    draw_list: &WindowDrawList,
+    time: &TimeBase,
+    origin: f64,
+    scale: f64,
+    width: f32,
+    bottom: f32,
+) {
+    let (rate, start) = match *time {
+        TimeBase::Rate { rate, start } => (rate, start),
+        _ => (1.0, 0.0),
+    };
+    let step = nice_step(AXIS_TICK_SPACING * scale / rate);
+    let decimals = (-step.log10().floor()).max(0.0) as usize;
 
-                        let high = (value >> 8) & 0b00011111;
-                        let low = value & 0b00011111;
+    let first = ((start + origin / rate) / step).ceil() as i64;
+    let last = ((start + (origin + scale * width as f64) / rate) / step).floor() as i64;
 
-                        let analog = (high << 5) | low;
+    // Time columns can be in any unit, so their precision follows the tick spacing
+    let column_decimals = match (time.at(first as f64 * step), time.at((first + 1) as f64 * step)) {
+        (Some(a), Some(b)) if b != a => (2.0 - (b - a).abs().log10().floor()).max(0.0) as usize,
+        _ => 3,
+    };
 
-                        let analog_flipped = 1024.0 - analog as f64;
+    for i in first..=last {
+        let t = i as f64 * step;
+        let x = (((t - start) * rate - origin) / scale) as f32;
+
+        let label = match *time {
+            TimeBase::Samples => format!("{:.*}", decimals, t),
+            TimeBase::Rate { .. } => format!("{:.*} s", decimals, t),
+            TimeBase::Column(_) => match time.at(t) {
+                Some(value) => format!("{:.*}", column_decimals, value),
+                None => continue,
+            },
+        };
+
+        draw_list
+            .add_line((x, bottom - 6.0), (x, bottom), 0xff808080)
+            .build();
+
+        ui.set_cursor_screen_pos((x + 2.0, bottom - 20.0));
+        ui.text(label);
+    }
+}
 
-                        let ch0_smooth_value = *ch0_smooth.lock().unwrap() as f64;
+/// Length of the longest channel.
+fn sample_count(channels: &[Channel]) -> usize {
+    channels
+        .iter()
+        .map(|channel| channel.samples().sample_count())
+        .fold(0, max)
+}
 
-                        ch0_avg = ch0_avg*ch0_smooth_value + analog_flipped*(1.0 - ch0_smooth_value);
+fn drag_overview(state: &mut State, drag: overview::Drag, count: usize, width: f64) {
+    let scale = f64::exp(state.scroll_factor);
+    let origin = scale * (state.pan.0 - width / 2.0);
+    let end = origin + scale * width;
 
-                        let time = {
-                            let time_packed = LittleEndian::read_u32(&buffer[2..6]);
+    let dx = (state.mouse_state.pos.0 - state.last_mouse_state.pos.0) as f64 * count as f64 / width;
 
-                            // The high bit in every byte of time_packed is 0 becouse of the sync bit 
-                            // in the high byte of value above. So we need to unpack this into a proper u32.
-                            // The bottom 4 bit of the u32 is discarded on the arduino to make room.
+    let (origin, end) = match drag {
+        overview::Drag::Move => (origin + dx, end + dx),
+        overview::Drag::Left => ((origin + dx).min(end - 1.0), end),
+        overview::Drag::Right => (origin, (end + dx).max(origin + 1.0)),
+    };
 
-                            let time = 
-                                ( ((time_packed >> 3) & 0x0fe0_0000)
-                                | ((time_packed >> 2) & 0x001f_c000)
-                                | ((time_packed >> 1) & 0x0000_3f80)
-                                | ((time_packed)      & 0x0000_007f)) << 4;
+    show_range(state, origin, end, width);
+}
 
-                            time
-                        };
+/// Shows the sample range from start to end across the view width.
+fn show_range(state: &mut State, start: f64, end: f64, width: f64) {
+    let scale = (end - start).max(1.0) / width;
 
-                        {
-                            if last_ligh_on == 1 && light_on == 0 {
-                                start_timestamp = time;
-                                measuring_cycle = true;
-                            }
+    state.scroll_factor = scale.ln();
+    state.pan.0 = start / scale + width / 2.0;
+    state.follow = false;
+}
+
+/// Zooms to follow_window and pans so that the newest sample is at the right edge.
+fn follow_newest_sample(state: &mut State, view_size: (f32, f32)) {
+    let newest = sample_count(&state.data.channels);
+
+    let scale = state.follow_window.max(1.0) as f64 / view_size.0 as f64;
+
+    state.scroll_factor = scale.ln();
+    state.pan.0 = newest as f64 / scale - view_size.0 as f64 / 2.0;
+}
+
+/// Vertical extent and y axis origin for each channel.
+fn lanes(state: &State, content_top: f64, view_size: (f32, f32)) -> Vec<Lane> {
+    match state.layout {
+        Layout::Overlay => {
+            let lane = Lane {
+                top: 0.0,
+                height: view_size.1 as f64,
+                center_y: view_size.1 as f64 / 2.0 - state.pan.1,
+            };
+
+            vec![lane; state.data.channels.len()]
+        }
+        Layout::Stacked => {
+            let height = view_size.1 as f64 - content_top;
+            let total_weight = state
+                .data
+                .channels
+                .iter()
+                .map(|channel| channel.lane_weight as f64)
+                .sum::<f64>();
+
+            let mut top = content_top;
+
+            state
+                .data
+                .channels
+                .iter()
+                .map(|channel| {
+                    let lane_height = height * channel.lane_weight as f64 / total_weight;
+                    let lane = Lane {
+                        top: top,
+                        height: lane_height,
+                        center_y: top + lane_height / 2.0,
+                    };
+                    top += lane_height;
+                    lane
+                })
+                .collect()
+        }
+    }
+}
+
+fn lane_drag_at(lanes: &[Lane], mouse_pos: (i32, i32)) -> Option<LaneDrag> {
+    let (x, y) = (mouse_pos.0 as f64, mouse_pos.1 as f64);
+
+    for (i, lane) in lanes.iter().enumerate() {
+        if i + 1 < lanes.len() && (y - (lane.top + lane.height)).abs() < 4.0 {
+            return Some(LaneDrag::Resize(i));
+        }
+    }
+
+    if x < LANE_HANDLE_WIDTH {
+        lanes.iter().position(|lane| lane.contains(y)).map(LaneDrag::Move)
+    } else {
+        None
+    }
+}
+
+fn drag_lane(state: &mut State, lanes: &[Lane], drag: LaneDrag) {
+    let mouse_y = state.mouse_state.pos.1 as f64;
+
+    match drag {
+        LaneDrag::Resize(i) => {
+            if i + 1 >= state.data.channels.len() {
+                return;
+            }
+
+            let total_weight = state
+                .data
+                .channels
+                .iter()
+                .map(|channel| channel.lane_weight as f64)
+                .sum::<f64>();
+            let total_height = lanes.iter().map(|lane| lane.height).sum::<f64>();
+
+            let weight_per_pixel = total_weight / total_height;
+            let dy = (state.mouse_state.pos.1 - state.last_mouse_state.pos.1) as f64;
+
+            let upper = state.data.channels[i].lane_weight as f64 + dy * weight_per_pixel;
+            let lower = state.data.channels[i + 1].lane_weight as f64 - dy * weight_per_pixel;
+            let min_weight = MIN_LANE_HEIGHT * weight_per_pixel;
+
+            if upper >= min_weight && lower >= min_weight {
+                state.data.channels[i].lane_weight = upper as f32;
+                state.data.channels[i + 1].lane_weight = lower as f32;
+            }
+        }
+        LaneDrag::Move(i) => {
+            let center = |lane: &Lane| lane.top + lane.height / 2.0;
+
+            if i > 0 && mouse_y < center(&lanes[i - 1]) {
+                state.data.channels.swap(i, i - 1);
+                state.lane_drag = Some(LaneDrag::Move(i - 1));
+            } else if i + 1 < lanes.len() && mouse_y > center(&lanes[i + 1]) {
+                state.data.channels.swap(i, i + 1);
+                state.lane_drag = Some(LaneDrag::Move(i + 1));
+            }
+        }
+    }
+}
+
+/// The channel in the lane under the mouse cursor, or in overlay layout the
+/// channel whose trace is closest to it.
+fn hovered_channel(state: &State, lanes: &[Lane], x_lookup: f64, scale: f64) -> Option<usize> {
+    let mouse_y = state.mouse_state.pos.1 as f64;
+    let y_scale = state.window_y_scale as f64;
+
+    if state.layout == Layout::Stacked {
+        return lanes.iter().position(|lane| lane.contains(mouse_y));
+    }
+
+    let mut best: Option<(usize, f64)> = None;
+
+    for (i, (channel, lane)) in state.data.channels.iter().zip(lanes.iter()).enumerate() {
+        let value = channel.samples().lookup(x_lookup, scale);
+
+        if let Some(value) = value {
+            let distance = (channel.value_to_y(value, lane.center_y, y_scale) - mouse_y).abs();
 
-                            if measuring_cycle && light_on == 1 {
-                                measuring_cycle = false;
-                            }
+            match best {
+                Some((_, best_distance)) if best_distance <= distance => (),
+                _ => best = Some((i, distance)),
+            }
+        }
+    }
 
-                            if measuring_cycle && ch0_avg < *rise_value.lock().unwrap() as f64 {
-                                measuring_cycle = false;
-                                let latency = time - start_timestamp;
-                                println!("{}", latency as f64 / 1000.0);
-                            }
+    best.map(|(i, _)| i)
+}
 
-                            last_ligh_on = light_on;
-                        }
+fn fit_channels(state: &mut State, lanes: &[Lane], start: f64, end: f64) {
+    let y_scale = state.window_y_scale as f64;
 
-                        {
-                            if block_ch0.data0.is_full() {
-                                blocks_ch0.lock().unwrap().push(block_ch0);
-                                block_ch0 = Box::new(Block::new());
-                            }
+    if state.lock_y_scale {
+        let range = state.data.channels.iter().fold(None, |range, channel| {
+            merge_range(range, channel.coarse_min_max(&*channel.samples(), start, end))
+        });
 
-                            if block_ch1.data0.is_full() {
-                                blocks_ch1.lock().unwrap().push(block_ch1);
-                                block_ch1 = Box::new(Block::new());
-                            }
+        // Shared scale has to fit in the smallest lane
+        let lane = lanes
+            .iter()
+            .fold(None, |smallest: Option<&Lane>, lane| match smallest {
+                Some(smallest) if smallest.height <= lane.height => Some(smallest),
+                _ => Some(lane),
+            });
 
-                            block_ch0.push(ch0_avg);
-                            block_ch1.push(if light_on == 1 { 100.0 } else { 0.0 });
-                        }
+        if let (Some(range), Some(lane)) = (range, lane) {
+            for channel in &mut state.data.channels {
+                channel.fit(range, lane.height, y_scale, lane.offset());
+            }
+        }
+    } else {
+        for (channel, lane) in state.data.channels.iter_mut().zip(lanes.iter()) {
+            let range = channel.coarse_min_max(&*channel.samples(), start, end);
 
-                        buffer.remove(0);
-                        buffer.remove(0);
-                        buffer.remove(0);
-                        buffer.remove(0);
-                        buffer.remove(0);
-                        buffer.remove(0);
-                    }
-                }
+            if let Some(range) = range {
+                channel.fit(range, lane.height, y_scale, lane.offset());
             }
+        }
+    }
+}
 
-            loading.store(false, Ordering::SeqCst);
-        }));
+/// Copies the y scale and pan of channels[source] to all other channels.
+fn share_y_scale(channels: &mut [Channel], source: usize) {
+    if let Some((pan, scale)) = channels.get(source).map(|c| (c.pan, c.scale)) {
+        for channel in channels.iter_mut() {
+            channel.pan = pan;
+            channel.scale = scale;
+        }
     }
 }
 
 fn run(ui: &Ui, state: &mut State) {
     let view_size = ui.imgui().display_size();
 
+    if state.clear_when_stopped && !state.loading.load(Ordering::SeqCst) {
+        state.clear_when_stopped = false;
+
+        for channel in &mut state.data.channels {
+            channel.blocks.lock().unwrap().clear();
+            channel.mapped = None;
+        }
+    }
+
+    open_queued(state);
+
+    if let Some((start, end)) = state.initial_view.take() {
+        show_range(state, start, end, view_size.0 as f64);
+    }
+
     ui.window(im_str!("Main"))
         .size(ui.imgui().display_size(), ImGuiCond::Always)
         .position((0.0, 0.0), ImGuiCond::Always)
@@ -407,11 +1968,22 @@ fn run(ui: &Ui, state: &mut State) {
                         .enabled(!state.loading.load(Ordering::SeqCst))
                         .build()
                     {
-                        if let Ok(nfd::Response::Okay(path)) =
-                            nfd::open_file_dialog(Some("txt"), None)
-                        {
+                        if let Some(path) = file_dialog(&state.notifications, "txt,csv,tsv,gz,zst,xz,wav,vcd,sr,npy,npz", false) {
+                            state.pan = (0.0, 0.0);
+                            open_path(&path, true, state);
+                        }
+                    }
+
+                    ui.checkbox(im_str!("Follow file"), &mut state.follow_file);
+
+                    if ui
+                        .menu_item(im_str!("Open binary"))
+                        .enabled(!state.loading.load(Ordering::SeqCst))
+                        .build()
+                    {
+                        if let Some(path) = file_dialog(&state.notifications, "bin,raw,dat", false) {
                             state.pan = (0.0, 0.0);
-                            open_file(&path, state);
+                            state.binary_import = Some(binary::Import::new(&path));
                         }
                     }
 
@@ -420,7 +1992,7 @@ fn run(ui: &Ui, state: &mut State) {
                         .enabled(!state.loading.load(Ordering::SeqCst))
                         .build()
                     {
-                        open_com_port(state);
+                        open_com_port(&PortSettings::new(), state);
                     }
 
                     if ui
@@ -431,20 +2003,124 @@ fn run(ui: &Ui, state: &mut State) {
                         state.pan = (0.0, 0.0);
                         state.scroll_factor = 0.0;
                         state.stop_loading.store(true, Ordering::SeqCst);
+                        state.clear_when_stopped = true;
+                    }
+
+                    ui.separator();
 
-                        while state.loading.load(Ordering::SeqCst) {
-                            thread::sleep(std::time::Duration::from_millis(1));
+                    if ui.menu_item(im_str!("Export WAV")).build() {
+                        let scale = f64::exp(state.scroll_factor);
+                        let origin = x_origin(state, view_size.0 as f64, scale);
+                        let rate = match state.data.time {
+                            TimeBase::Rate { rate, .. } => Some(rate),
+                            _ => None,
+                        };
+
+                        state.wav_export = Some(export::Export::new(
+                            state.data.shown(),
+                            origin,
+                            origin + scale * view_size.0 as f64,
+                            sample_count(&state.data.channels),
+                            wav::Options::new(rate),
+                        ));
+                    }
+
+                    if ui.menu_item(im_str!("Export NumPy")).build() {
+                        let scale = f64::exp(state.scroll_factor);
+                        let origin = x_origin(state, view_size.0 as f64, scale);
+
+                        state.npy_export = Some(export::Export::new(
+                            state.data.shown(),
+                            origin,
+                            origin + scale * view_size.0 as f64,
+                            sample_count(&state.data.channels),
+                            npy::Options,
+                        ));
+                    }
+
+                    if ui.menu_item(im_str!("Load session")).build() {
+                        if let Some(path) = file_dialog(&state.notifications, "session", false) {
+                            match session::Session::load(&path) {
+                                Ok(session) => session.apply(state),
+                                Err(e) => state.notifications.lock().unwrap().error(&path, e),
+                            }
                         }
+                    }
 
-                        state.data.blocks_ch0.lock().unwrap().clear();
-                        state.data.blocks_ch1.lock().unwrap().clear();
+                    if ui.menu_item(im_str!("Save session")).build() {
+                        if let Some(path) = file_dialog(&state.notifications, "session", true) {
+                            if let Err(e) = session::Session::capture(state).save(&path) {
+                                state.notifications.lock().unwrap().error(&path, e);
+                            }
+                        }
                     }
                 });
             });
 
             let menu_bar_hovered = ui.is_item_hovered();
+            let view_hovered = !menu_bar_hovered && ui.is_window_hovered();
+
+            let content_top = ui.get_cursor_screen_pos().1 as f64;
+            let plot_size = if state.show_overview {
+                (view_size.0, view_size.1 - overview::HEIGHT)
+            } else {
+                view_size
+            };
+
+            let lanes = lanes(state, content_top, plot_size);
+            let y_scale = state.window_y_scale as f64;
+
+            for channel in &mut state.data.channels {
+                channel.update_summary();
+            }
+
+            let count = sample_count(&state.data.channels);
+
+            if view_hovered
+                && state.show_overview
+                && state.mouse_state.pos.1 as f32 >= plot_size.1
+                && state.mouse_state.pressed.0
+                && !state.last_mouse_state.pressed.0
+            {
+                let scale = f64::exp(state.scroll_factor);
+                let origin = scale * (state.pan.0 - view_size.0 as f64 / 2.0);
+                let rect = overview::view_rect(origin, scale, view_size.0, count);
+                let mouse_x = state.mouse_state.pos.0 as f32;
+
+                state.overview_drag = match overview::drag_at(mouse_x, rect) {
+                    Some(drag) => Some(drag),
+                    None => {
+                        // Center the view on the pressed position and keep dragging from there
+                        state.pan.0 = mouse_x as f64 * count as f64 / view_size.0 as f64 / scale;
+                        state.follow = false;
+                        Some(overview::Drag::Move)
+                    }
+                };
+            }
+
+            if !state.mouse_state.pressed.0 {
+                state.overview_drag = None;
+            }
+
+            if let Some(drag) = state.overview_drag {
+                drag_overview(state, drag, count, view_size.0 as f64);
+            }
+
+            if view_hovered
+                && state.layout == Layout::Stacked
+                && state.mouse_state.pressed.0
+                && !state.last_mouse_state.pressed.0
+            {
+                state.lane_drag = lane_drag_at(&lanes, state.mouse_state.pos);
+            }
 
-            if !menu_bar_hovered && ui.is_window_hovered() && state.mouse_state.pressed.0 {
+            if !state.mouse_state.pressed.0 {
+                state.lane_drag = None;
+            }
+
+            if let Some(drag) = state.lane_drag {
+                drag_lane(state, &lanes, drag);
+            } else if view_hovered && state.mouse_state.pressed.0 && state.overview_drag.is_none() {
                 state.panning = true;
             }
 
@@ -452,15 +2128,54 @@ fn run(ui: &Ui, state: &mut State) {
                 state.panning = false;
             }
 
-            if (!menu_bar_hovered && ui.is_window_hovered()) || state.panning {
+            if (view_hovered && state.lane_drag.is_none() && state.overview_drag.is_none())
+                || state.panning
+            {
                 if state.mouse_state.pressed.0 {
-                    state.pan.0 +=
-                        state.last_mouse_state.pos.0 as f64 - state.mouse_state.pos.0 as f64;
-                    state.pan.1 +=
-                        state.last_mouse_state.pos.1 as f64 - state.mouse_state.pos.1 as f64;
+                    let dx = state.last_mouse_state.pos.0 as f64 - state.mouse_state.pos.0 as f64;
+                    let dy = state.last_mouse_state.pos.1 as f64 - state.mouse_state.pos.1 as f64;
+
+                    state.pan.0 += dx;
+
+                    if dx < 0.0 {
+                        state.follow = false;
+                    }
+
+                    match state.layout {
+                        Layout::Overlay => state.pan.1 += dy,
+                        Layout::Stacked => {
+                            let mouse_y = state.mouse_state.pos.1 as f64;
+
+                            if let Some(i) = lanes.iter().position(|lane| lane.contains(mouse_y)) {
+                                let channel = &mut state.data.channels[i];
+                                channel.pan += (dy / (y_scale * channel.scale as f64)) as f32;
+                            }
+                        }
+                    }
                 }
 
-                if state.mouse_state.wheel != 0.0 {
+                if state.mouse_state.wheel != 0.0 && ui.imgui().key_ctrl() {
+                    let scale = f64::exp(state.scroll_factor);
+                    let mouse_x_lookup = x_origin(state, view_size.0 as f64, scale)
+                        + scale * state.mouse_state.pos.0 as f64;
+
+                    if let Some(index) = hovered_channel(state, &lanes, mouse_x_lookup, scale) {
+                        let factor = f64::exp(state.mouse_state.wheel as f64 / 10.0);
+                        let mouse_value = state.data.channels[index].y_to_value(
+                            state.mouse_state.pos.1 as f64,
+                            lanes[index].center_y,
+                            y_scale,
+                        );
+
+                        if state.lock_y_scale {
+                            for channel in &mut state.data.channels {
+                                channel.zoom_y(mouse_value, factor);
+                            }
+                        } else {
+                            state.data.channels[index].zoom_y(mouse_value, factor);
+                        }
+                    }
+                } else if state.mouse_state.wheel != 0.0 {
                     let mouse_centered_x =
                         state.mouse_state.pos.0 as f64 - view_size.0 as f64 / 2.0;
 
@@ -477,114 +2192,224 @@ fn run(ui: &Ui, state: &mut State) {
                         (mouse_centered_last_scale_x - mouse_centered_scale_x) * last_scale;
 
                     state.scroll_factor = new_scroll_factor;
+                    state.follow_window = (new_scale * view_size.0 as f64) as f32;
                 }
             }
 
+            if state.follow {
+                follow_newest_sample(state, view_size);
+            }
+
             let scale = f64::exp(state.scroll_factor as f64);
 
+            if state.scope.enabled {
+                if let Some(channel) = state.data.channels.get(state.scope.channel) {
+                    let blocks = channel.samples();
+                    state.scope.update(&*blocks, scale * view_size.0 as f64);
+                }
+            }
+
+            let origin = x_origin(state, view_size.0 as f64, scale);
+
+            // Lanes may have been resized or reordered above
+            let lanes = self::lanes(state, content_top, plot_size);
+
+            if state.auto_fit {
+                let end = origin + scale * view_size.0 as f64;
+                fit_channels(state, &lanes, origin, end);
+            }
+
             {
                 let draw_list = ui.get_window_draw_list();
 
-                {
-                    let blocks_ch0 = state.data.blocks_ch0.lock().unwrap();
+                let shown = state.data.shown();
 
-                    {
-                        let capacity = state.data.points_ch0.capacity();
-                        state.data.points_ch0.clear();
-                        state
-                            .data
-                            .points_ch0
-                            .reserve_exact(max(capacity as i32 - view_size.0 as i32, 0) as usize);
+                if state.density.enabled {
+                    let sweep = if state.scope.enabled {
+                        Some(state.scope.sweeps)
+                    } else {
+                        None
+                    };
+
+                    state.density.accumulate(
+                        sweep,
+                        &state.data.channels,
+                        &shown,
+                        &lanes,
+                        origin,
+                        scale,
+                        y_scale,
+                        plot_size,
+                    );
+
+                    if let Some(texture) = state.density.texture() {
+                        ui.set_cursor_screen_pos((0.0, 0.0));
+                        ui.image(texture, plot_size).build();
                     }
+                } else {
+                    for ((channel, lane), &shown) in state
+                        .data
+                        .channels
+                        .iter_mut()
+                        .zip(lanes.iter())
+                        .zip(shown.iter())
+                    {
+                        if !shown {
+                            continue;
+                        }
 
-                    for x in 0..(view_size.0 as i32) {
-                        let x_lookup = scale * (x as f64 + state.pan.0 - view_size.0 as f64 / 2.0);
-
-                        if let Some(value) = blocks_ch0.lookup(x_lookup, scale) {
-                            state.data.points_ch0.push(ImVec2::new(
-                                x as f32,
-                                ((state.ch0_scale as f64
-                                    * state.window_y_scale as f64
-                                    * (value + state.ch0_pan as f64))
-                                    + state.pan.1
-                                    - view_size.1 as f64 / 2.0)
-                                    as f32,
-                            ));
+                        if channel.style == DrawStyle::Bus {
+                            draw_list.with_clip_rect_intersect(
+                                (0.0, lane.top as f32),
+                                (view_size.0, (lane.top + lane.height) as f32),
+                                || draw_bus(ui, &draw_list, channel, lane, origin, scale, view_size.0),
+                            );
+                            continue;
                         }
-                    }
 
-                    if state.data.points_ch0.len() > 1 {
-                        for (p1, p2) in state
-                            .data
-                            .points_ch0
-                            .iter()
-                            .zip(state.data.points_ch0[1..].iter())
+                        // Taken out while the samples are borrowed from the channel
+                        let mut points = ::std::mem::replace(&mut channel.points, Vec::new());
+
                         {
-                            draw_list
-                                .add_line((p1.x, -p1.y), (p2.x, -p2.y), 0xdf00dfff)
-                                .build();
+                            let capacity = points.capacity();
+                            points.clear();
+                            points.reserve_exact(max(capacity as i32 - view_size.0 as i32, 0) as usize);
                         }
-                    }
-                }
 
-                {
-                    let blocks_ch1 = state.data.blocks_ch1.lock().unwrap();
+                        let blocks = channel.samples();
 
-                    {
-                        let capacity = state.data.points_ch1.capacity();
-                        state.data.points_ch1.clear();
-                        state
-                            .data
-                            .points_ch1
-                            .reserve_exact(max(capacity as i32 - view_size.0 as i32, 0) as usize);
+                        if scale < 1.0 {
+                            // Fewer samples than columns, connect the actual samples
+                            let first = origin.floor().max(0.0) as usize;
+                            let last = (origin + scale * view_size.0 as f64).ceil().max(0.0) as usize;
+
+                            for i in first..min(last + 1, blocks.sample_count()) {
+                                let x = ((i as f64 - origin) / scale) as f32;
+
+                                match blocks.lookup(i as f64, scale) {
+                                    Some(value) if value.is_finite() => {
+                                        let y = channel.value_to_y(value, lane.center_y, y_scale);
+                                        points.push(ImVec2::new(x, y as f32));
+                                    }
+                                    _ => push_gap(&mut points, x),
+                                }
+                            }
+                        } else {
+                            for x in 0..(view_size.0 as i32) {
+                                let x_lookup = origin + scale * x as f64;
+
+                                if channel.style == DrawStyle::Logic {
+                                    if let Some((low, high)) = channel.coarse_min_max(&*blocks, x_lookup, x_lookup + scale) {
+                                        let last_y = points.last().map(|p| p.y);
+                                        let low_y = channel.value_to_y(low, lane.center_y, y_scale);
+                                        let high_y = channel.value_to_y(high, lane.center_y, y_scale);
+
+                                        // Start the column at the level the previous one ended on
+                                        if last_y == Some(low_y as f32) {
+                                            points.push(ImVec2::new(x as f32, low_y as f32));
+                                            points.push(ImVec2::new(x as f32, high_y as f32));
+                                        } else {
+                                            points.push(ImVec2::new(x as f32, high_y as f32));
+                                            points.push(ImVec2::new(x as f32, low_y as f32));
+                                        }
+                                    } else {
+                                        push_gap(&mut points, x as f32);
+                                    }
+                                } else {
+                                    match blocks.lookup(x_lookup, scale) {
+                                        Some(value) if value.is_finite() => {
+                                            let y = channel.value_to_y(value, lane.center_y, y_scale);
+                                            points.push(ImVec2::new(x as f32, y as f32));
+                                        }
+                                        _ => push_gap(&mut points, x as f32),
+                                    }
+                                }
+                            }
+                        }
+
+                        drop(blocks);
+
+                        let style = channel.style;
+                        let color = channel.packed_color();
+                        let width = channel.line_width;
+                        let dots = 1.0 / scale >= SAMPLE_DOT_SPACING;
+
+                        draw_list.with_clip_rect_intersect(
+                            (0.0, lane.top as f32),
+                            (view_size.0, (lane.top + lane.height) as f32),
+                            || draw_trace(&draw_list, &points, style, color, width, dots),
+                        );
+
+                        channel.points = points;
                     }
+                }
 
-                    for x in 0..(view_size.0 as i32) {
-                        let x_lookup = scale * (x as f64 + state.pan.0 - view_size.0 as f64 / 2.0);
-
-                        if let Some(value) = blocks_ch1.lookup(x_lookup, scale) {
-                            state.data.points_ch1.push(ImVec2::new(
-                                x as f32,
-                                ((state.ch1_scale as f64
-                                    * state.window_y_scale as f64
-                                    * (value + state.ch1_pan as f64))
-                                    + state.pan.1
-                                    - view_size.1 as f64 / 2.0)
-                                    as f32,
-                            ));
+                match state.layout {
+                    Layout::Overlay => {
+                        for i in -9..10 {
+                            let x1 = 0.0;
+                            let x2 = view_size.0 as f32;
+                            let y = (state.pan.1 - view_size.1 as f64 / 2.0) as f32
+                                + (i as f32) * state.window_y_scale * 100.0;
+
+                            if i == 0 {
+                                draw_list.add_line((x1, -y), (x2, -y), 0x602a2aff).build();
+                            } else if i < 0 {
+                                draw_list.add_line((x1, -y), (x2, -y), 0x2a2a2aff).build();
+                            } else {
+                                draw_list.add_line((x1, -y), (x2, -y), 0x2a2a2aff).build();
+                            }
                         }
                     }
+                    Layout::Stacked => {
+                        for (channel, lane) in state.data.channels.iter().zip(lanes.iter()) {
+                            let top = lane.top as f32;
+                            let bottom = (lane.top + lane.height) as f32;
+                            let zero_y = channel.value_to_y(0.0, lane.center_y, y_scale) as f32;
+
+                            if zero_y > top && zero_y < bottom {
+                                draw_list
+                                    .add_line((0.0, zero_y), (view_size.0, zero_y), 0x602a2aff)
+                                    .build();
+                            }
+
+                            draw_list
+                                .add_line((0.0, bottom), (view_size.0, bottom), 0xff808080)
+                                .build();
 
-                    if state.data.points_ch0.len() > 1 {
-                        for (p1, p2) in state
-                            .data
-                            .points_ch1
-                            .iter()
-                            .zip(state.data.points_ch1[1..].iter())
-                        {
                             draw_list
-                                .add_line((p1.x, -p1.y), (p2.x, -p2.y), 0xdf1010ff)
+                                .add_rect((0.0, top), (LANE_HANDLE_WIDTH as f32, bottom), 0x40ffffff)
+                                .filled(true)
                                 .build();
+
+                            let top_value = channel.y_to_value(lane.top, lane.center_y, y_scale);
+                            let bottom_value =
+                                channel.y_to_value(lane.top + lane.height, lane.center_y, y_scale);
+
+                            ui.set_cursor_screen_pos((LANE_HANDLE_WIDTH as f32 + 4.0, top + 2.0));
+                            ui.text(im_str!("{} [{:.3}, {:.3}]", channel.name, bottom_value, top_value));
                         }
                     }
                 }
 
-                for i in -9..10 {
-                    let x1 = 0.0;
-                    let x2 = view_size.0 as f32;
-                    let y = (state.pan.1 - view_size.1 as f64 / 2.0) as f32
-                        + (i as f32) * state.window_y_scale * 100.0;
+                if let Some(trigger) = state.scope.trigger().filter(|_| state.scope.enabled) {
+                    let x = ((trigger as f64 - origin) / scale) as f32;
 
-                    if i == 0 {
-                        draw_list.add_line((x1, -y), (x2, -y), 0x602a2aff).build();
-                    } else if i < 0 {
-                        draw_list.add_line((x1, -y), (x2, -y), 0x2a2a2aff).build();
-                    } else {
-                        draw_list.add_line((x1, -y), (x2, -y), 0x2a2a2aff).build();
+                    draw_list
+                        .add_line((x, 0.0), (x, view_size.1), 0xa02affff)
+                        .build();
+
+                    if let Some(channel) = state.data.channels.get(state.scope.channel) {
+                        let lane = &lanes[state.scope.channel];
+                        let y = channel.value_to_y(state.scope.level as f64, lane.center_y, y_scale) as f32;
+
+                        draw_list
+                            .add_line((0.0, y), (view_size.0, y), 0xa02affff)
+                            .build();
                     }
                 }
 
-                {
+                if state.layout == Layout::Overlay {
                     let x1 = 0.0;
                     let x2 = view_size.0 as f32;
                     let rise_y = (state.pan.1 - view_size.1 as f64 / 2.0) as f32
@@ -594,6 +2419,13 @@ fn run(ui: &Ui, state: &mut State) {
                         .add_line((x1, -rise_y), (x2, -rise_y), 0xa0ff2a2a)
                         .build();
                 }
+
+                draw_time_axis(ui, &draw_list, &state.data.time, origin, scale, view_size.0, plot_size.1);
+
+                if state.show_overview {
+                    let rect = overview::view_rect(origin, scale, view_size.0, count);
+                    overview::draw(&draw_list, &state.data.channels, plot_size.1, view_size.0, count, rect);
+                }
             }
 
             ui.with_style_vars(
@@ -607,12 +2439,75 @@ fn run(ui: &Ui, state: &mut State) {
                         .title_bar(true)
                         .collapsible(true)
                         .build(|| {
-                            ui.drag_float(im_str!("Ch 0 pan"), &mut state.ch0_pan)
-                                .speed(0.1)
-                                .build();
-                            ui.drag_float(im_str!("Ch 0 scale"), &mut state.ch0_scale)
-                                .speed(0.001)
-                                .build();
+                            {
+                                let mut layout = state.layout as i32;
+
+                                if ui.combo(
+                                    im_str!("Layout"),
+                                    &mut layout,
+                                    &[im_str!("Overlay"), im_str!("Stacked")],
+                                    2,
+                                ) {
+                                    state.layout = match layout {
+                                        1 => Layout::Stacked,
+                                        _ => Layout::Overlay,
+                                    };
+                                }
+                            }
+
+                            let mut changed = None;
+
+                            for (i, channel) in state.data.channels.iter_mut().enumerate() {
+                                ui.with_id(i as i32, || {
+                                    if ui.drag_float(&im_str!("{} pan", channel.name), &mut channel.pan)
+                                        .speed(0.1)
+                                        .build()
+                                    {
+                                        changed = Some(i);
+                                    }
+                                    if ui.drag_float(&im_str!("{} scale", channel.name), &mut channel.scale)
+                                        .speed(0.001)
+                                        .build()
+                                    {
+                                        changed = Some(i);
+                                    }
+
+                                    let mut digital = channel.digital;
+                                    if ui.checkbox(im_str!("Digital"), &mut digital) {
+                                        channel.set_digital(digital);
+                                    }
+                                    ui.same_line(0.0);
+
+                                    let mut style = channel.style as i32;
+                                    if ui.combo(
+                                        im_str!("Style"),
+                                        &mut style,
+                                        &[
+                                            im_str!("Line"),
+                                            im_str!("Step"),
+                                            im_str!("Points"),
+                                            im_str!("Logic"),
+                                            im_str!("Bus"),
+                                        ],
+                                        5,
+                                    ) {
+                                        channel.style = match style {
+                                            1 => DrawStyle::Step,
+                                            2 => DrawStyle::Points,
+                                            3 => DrawStyle::Logic,
+                                            4 => DrawStyle::Bus,
+                                            _ => DrawStyle::Line,
+                                        };
+                                    }
+                                });
+                            }
+
+                            if let Some(i) = changed {
+                                if state.lock_y_scale {
+                                    share_y_scale(&mut state.data.channels, i);
+                                }
+                            }
+
                             if ui.drag_float(im_str!("Ch 0 smooth"), &mut state.ch0_smooth.lock().unwrap())
                                 .speed(0.001)
                                 .min(0.0)
@@ -622,12 +2517,47 @@ fn run(ui: &Ui, state: &mut State) {
                                     *lock = clamp(0.0, *lock, 1.0);
                                 }
 
-                            ui.drag_float(im_str!("Ch 1 pan"), &mut state.ch1_pan)
-                                .speed(0.1)
-                                .build();
-                            ui.drag_float(im_str!("Ch 1 scale"), &mut state.ch1_scale)
-                                .speed(0.001)
-                                .build();
+                            if ui.button(im_str!("Fit visible"), (0.0, 0.0)) {
+                                let end = origin + scale * view_size.0 as f64;
+                                fit_channels(state, &lanes, origin, end);
+                            }
+                            ui.same_line(0.0);
+                            if ui.button(im_str!("Fit all"), (0.0, 0.0)) {
+                                fit_channels(state, &lanes, 0.0, std::f64::MAX);
+                            }
+                            ui.same_line(0.0);
+                            ui.checkbox(im_str!("Auto fit"), &mut state.auto_fit);
+
+                            ui.checkbox(im_str!("Overview"), &mut state.show_overview);
+
+                            ui.checkbox(im_str!("Follow"), &mut state.follow);
+                            ui.same_line(0.0);
+                            if ui.drag_float(im_str!("Follow window"), &mut state.follow_window)
+                                .speed(10.0)
+                                .min(1.0)
+                                .build()
+                            {
+                                state.follow_window = state.follow_window.max(1.0);
+                            }
+
+                            if ui.checkbox(im_str!("Lock Y scale"), &mut state.lock_y_scale)
+                                && state.lock_y_scale
+                            {
+                                share_y_scale(&mut state.data.channels, 0);
+                            }
+
+                            if ui.collapsing_header(im_str!("Scope")).build() {
+                                state.scope.properties(ui, &state.data.channels);
+                            }
+
+                            if ui.collapsing_header(im_str!("Density")).build() {
+                                ui.checkbox(im_str!("Density mode"), &mut state.density.enabled);
+                                ui.slider_float(im_str!("Decay"), &mut state.density.decay, 0.0, 1.0)
+                                    .build();
+                                if ui.button(im_str!("Clear"), (0.0, 0.0)) {
+                                    state.density.clear();
+                                }
+                            }
 
                             ui.drag_float(im_str!("Window Y Scale"), &mut state.window_y_scale)
                                 .speed(0.001)
@@ -644,13 +2574,246 @@ fn run(ui: &Ui, state: &mut State) {
                             ));
                             ui.text(im_str!("Zoom {:?}", scale));
 
+                            if let Some(report) = *state.load_report.lock().unwrap() {
+                                let megabytes = report.bytes as f64 / 1e6;
+
+                                ui.text(im_str!(
+                                    "Loaded {:.1} of {:.1} MB in {:.2} s, {:.1} MB/s",
+                                    megabytes,
+                                    report.total as f64 / 1e6,
+                                    report.seconds,
+                                    megabytes / report.seconds.max(1e-9)
+                                ));
+                            }
+
                             ui.text(im_str!("{:#?}", state));
                         });
+
+                    ui.window(im_str!("Legend"))
+                        .size((320.0, 200.0), ImGuiCond::FirstUseEver)
+                        .position((view_size.0 - 345.0, 50.0), ImGuiCond::FirstUseEver)
+                        .movable(true)
+                        .resizable(true)
+                        .title_bar(true)
+                        .collapsible(true)
+                        .build(|| {
+                            let mouse_x_lookup = origin + scale * state.mouse_state.pos.0 as f64;
+
+                            let x = if view_hovered {
+                                mouse_x_lookup
+                            } else {
+                                sample_count(&state.data.channels) as f64 - 1.0
+                            };
+
+                            if let Some(time) = state.data.time.at(x.floor()) {
+                                ui.text(im_str!("Time: {:.6}", time));
+                                ui.separator();
+                            }
+
+                            for (i, channel) in state.data.channels.iter_mut().enumerate() {
+                                ui.with_id(i as i32, || {
+                                    ui.checkbox(im_str!("##visible"), &mut channel.visible);
+                                    ui.same_line(0.0);
+                                    ui.color_edit(im_str!("##color"), &mut channel.color)
+                                        .inputs(false)
+                                        .build();
+                                    ui.same_line(0.0);
+
+                                    let mut name = ImString::with_capacity(64);
+                                    name.push_str(&channel.name);
+
+                                    ui.with_item_width(120.0, || {
+                                        if ui.input_text(im_str!("##name"), &mut name).build() {
+                                            channel.name = name.to_str().to_owned();
+                                        }
+                                    });
+                                    ui.same_line(0.0);
+                                    ui.checkbox(im_str!("Solo"), &mut channel.solo);
+
+                                    ui.with_item_width(120.0, || {
+                                        ui.drag_float(im_str!("Width"), &mut channel.line_width)
+                                            .speed(0.05)
+                                            .min(0.5)
+                                            .max(8.0)
+                                            .build();
+                                    });
+                                    ui.same_line(0.0);
+
+                                    let value = {
+                                        let blocks = channel.samples();
+
+                                        // Value under the cursor, or the newest one when not hovering the plot
+                                        if view_hovered {
+                                            blocks.lookup(mouse_x_lookup, scale)
+                                        } else {
+                                            let count = blocks.sample_count();
+                                            blocks.lookup(count as f64 - 1.0, scale)
+                                        }
+                                    };
+
+                                    ui.text(match value {
+                                        Some(value) => format!("{:.4}", value),
+                                        None => "-".to_owned(),
+                                    });
+
+                                    ui.separator();
+                                });
+                            }
+                        });
+
+                    let import = state.csv_preview.as_mut().and_then(|preview| preview.window(ui));
+
+                    if let Some(load) = import {
+                        if let Some(preview) = state.csv_preview.take() {
+                            if load {
+                                open_csv(&preview.path, &preview.format, None, state);
+                            }
+                        }
+                    }
+
+                    export::show(ui, &mut state.wav_export, &state.data.channels, &state.notifications, &state.waker);
+                    export::show(ui, &mut state.npy_export, &state.data.channels, &state.notifications, &state.waker);
+
+                    let import = state.binary_import.as_mut().and_then(|import| import.window(ui));
+
+                    if let Some(load) = import {
+                        if let Some(import) = state.binary_import.take() {
+                            if load && import.map {
+                                open_mapped(&import.path, &import.format, state);
+                            } else if load {
+                                open_binary(&import.path, &import.format, state);
+                            }
+                        }
+                    }
+
+                    if state.loading.load(Ordering::SeqCst) {
+                        progress_window(ui, state, view_size);
+                    }
+
+                    state.notifications.lock().unwrap().window(ui, view_size);
                 },
             );
         });
 }
 
+/// Feeds a window event to imgui and the mouse state. Returns true if the
+/// event should cause a redraw.
+fn handle_event(
+    event: Event,
+    s: &mut State,
+    imgui: &mut ImGui,
+    display: &Display,
+    new_absolute_mouse_pos: &mut Option<(i32, i32)>,
+) -> bool {
+    use glium::glutin::{
+        DeviceEvent, ElementState, MouseButton, MouseScrollDelta, TouchPhase, WindowEvent,
+    };
+
+    let redraw = match event {
+        Event::DeviceEvent { .. } => false,
+        _ => true,
+    };
+
+    match event {
+        Event::DeviceEvent { event, .. } => match event {
+            DeviceEvent::MouseMotion { delta: (x, y), .. } => {
+                s.mouse_state.pos.0 += x as i32;
+                s.mouse_state.pos.1 += y as i32;
+            }
+            _ => (),
+        },
+
+        Event::WindowEvent { event, .. } => match event {
+            WindowEvent::CloseRequested => {
+                s.quit = true;
+            }
+            WindowEvent::Resized(LogicalSize { width, height }) => {
+                display.gl_window().resize((width, height).into());
+            }
+            WindowEvent::KeyboardInput { input, .. } => {
+                use glium::glutin::VirtualKeyCode as Key;
+
+                let pressed = input.state == ElementState::Pressed;
+                match input.virtual_keycode {
+                    Some(Key::Tab) => imgui.set_key(0, pressed),
+                    Some(Key::Left) => imgui.set_key(1, pressed),
+                    Some(Key::Right) => imgui.set_key(2, pressed),
+                    Some(Key::Up) => imgui.set_key(3, pressed),
+                    Some(Key::Down) => imgui.set_key(4, pressed),
+                    Some(Key::PageUp) => imgui.set_key(5, pressed),
+                    Some(Key::PageDown) => imgui.set_key(6, pressed),
+                    Some(Key::Home) => imgui.set_key(7, pressed),
+                    Some(Key::End) => imgui.set_key(8, pressed),
+                    Some(Key::Delete) => imgui.set_key(9, pressed),
+                    Some(Key::Back) => imgui.set_key(10, pressed),
+                    Some(Key::Return) => imgui.set_key(11, pressed),
+                    Some(Key::Escape) => imgui.set_key(12, pressed),
+                    Some(Key::A) => imgui.set_key(13, pressed),
+                    Some(Key::C) => imgui.set_key(14, pressed),
+                    Some(Key::V) => imgui.set_key(15, pressed),
+                    Some(Key::X) => imgui.set_key(16, pressed),
+                    Some(Key::Y) => imgui.set_key(17, pressed),
+                    Some(Key::Z) => imgui.set_key(18, pressed),
+                    Some(Key::LControl) | Some(Key::RControl) => {
+                        imgui.set_key_ctrl(pressed)
+                    }
+                    Some(Key::LShift) | Some(Key::RShift) => imgui.set_key_shift(pressed),
+                    Some(Key::LAlt) | Some(Key::RAlt) => imgui.set_key_alt(pressed),
+                    Some(Key::LWin) | Some(Key::RWin) => imgui.set_key_super(pressed),
+                    _ => {}
+                }
+            }
+            WindowEvent::CursorMoved {
+                position: LogicalPosition { x, y },
+                ..
+            } => {
+                if x as i32 != 0 && y as i32 != 0 {
+                    *new_absolute_mouse_pos = Some((x as i32, y as i32));
+                }
+            }
+            WindowEvent::MouseInput { state, button, .. } => match button {
+                MouseButton::Left => {
+                    s.mouse_state.pressed.0 = state == ElementState::Pressed
+                }
+                MouseButton::Right => {
+                    s.mouse_state.pressed.1 = state == ElementState::Pressed
+                }
+                MouseButton::Middle => {
+                    s.mouse_state.pressed.2 = state == ElementState::Pressed
+                }
+                _ => {}
+            },
+            WindowEvent::MouseWheel {
+                delta: MouseScrollDelta::LineDelta(_, y),
+                phase: TouchPhase::Moved,
+                ..
+            } => {
+                s.mouse_state.wheel = y;
+            }
+            WindowEvent::MouseWheel {
+                delta: MouseScrollDelta::PixelDelta(LogicalPosition { y, .. }),
+                phase: TouchPhase::Moved,
+                ..
+            } => {
+                s.mouse_state.wheel = y as f32;
+            }
+            WindowEvent::ReceivedCharacter(c) => imgui.add_input_character(c),
+            _ => (),
+        },
+        _ => (),
+    }
+
+    if redraw {
+        s.redraw_frames = REDRAW_FRAMES;
+    }
+
+    redraw
+}
+
+fn mouse_pressed(s: &State) -> bool {
+    s.mouse_state.pressed.0 || s.mouse_state.pressed.1 || s.mouse_state.pressed.2
+}
+
 #[cfg(windows)]
 fn detect_mouse_button_release_outside_window(state: &mut State) {
     use winapi::um::winuser::GetAsyncKeyState;
@@ -667,6 +2830,18 @@ fn detect_mouse_button_release_outside_window(state: &mut State) {
 fn detect_mouse_button_release_outside_window(_state: &mut State) {}
 
 fn main() {
+    let options = match cli::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    if options.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
 
     let icon = image::open("icon/plotter-rs.png").unwrap().to_rgba();
     let (icon_w, icon_h) = (icon.width(), icon.height());
@@ -676,7 +2851,8 @@ fn main() {
     let display = {
         let context = ContextBuilder::new()
             .with_gl_profile(GlProfile::Core)
-            .with_gl(GlRequest::Specific(Api::OpenGl, (4, 3)));
+            .with_gl(GlRequest::Specific(Api::OpenGl, (4, 3)))
+            .with_vsync(true);
         let window = WindowBuilder::new()
             .with_title("plotter-rs")
             .with_dimensions((1024u32, 768u32).into())
@@ -711,112 +2887,43 @@ fn main() {
     imgui.set_imgui_key(ImGuiKey::Z, 18);
 
     let mut s = State::new();
+    s.waker = Waker::new(Some(events_loop.create_proxy()));
 
-    let mut begin_frame;
+    s.follow_file = options.follow;
+    s.initial_view = options.view;
+    s.open_queue = options.sources.into_iter().collect();
 
-    loop {
+    // Applied after queueing, so that the sources get the channel properties
+    if let Some(ref path) = options.session {
+        match session::Session::load(path) {
+            Ok(session) => session.apply(&mut s),
+            Err(e) => s.notifications.lock().unwrap().error(path, e),
+        }
+    }
 
-        begin_frame = time::precise_time_s();
+    let mut begin_frame;
 
+    loop {
         s.last_mouse_state = s.mouse_state;
         s.mouse_state.wheel = 0.0;
 
         let mut new_absolute_mouse_pos = None;
 
-        events_loop.poll_events(|event| {
-            use glium::glutin::{
-                DeviceEvent, ElementState, Event, MouseButton, MouseScrollDelta, TouchPhase,
-                WindowEvent,
-            };
+        if s.redraw_frames == 0 && !mouse_pressed(&s) {
+            // Nothing is animating, sleep until there is input or new data
+            events_loop.run_forever(|event| {
+                if handle_event(event, &mut s, &mut imgui, &display, &mut new_absolute_mouse_pos) {
+                    ControlFlow::Break
+                } else {
+                    ControlFlow::Continue
+                }
+            });
+        }
 
-            match event {
-                Event::DeviceEvent { event, .. } => match event {
-                    DeviceEvent::MouseMotion { delta: (x, y), .. } => {
-                        s.mouse_state.pos.0 += x as i32;
-                        s.mouse_state.pos.1 += y as i32;
-                    }
-                    _ => (),
-                },
+        begin_frame = time::precise_time_s();
 
-                Event::WindowEvent { event, .. } => match event {
-                    WindowEvent::CloseRequested => {
-                        s.quit = true;
-                    }
-                    WindowEvent::Resized(LogicalSize { width, height }) => {
-                        display.gl_window().resize((width, height).into());
-                    }
-                    WindowEvent::KeyboardInput { input, .. } => {
-                        use glium::glutin::VirtualKeyCode as Key;
-
-                        let pressed = input.state == ElementState::Pressed;
-                        match input.virtual_keycode {
-                            Some(Key::Tab) => imgui.set_key(0, pressed),
-                            Some(Key::Left) => imgui.set_key(1, pressed),
-                            Some(Key::Right) => imgui.set_key(2, pressed),
-                            Some(Key::Up) => imgui.set_key(3, pressed),
-                            Some(Key::Down) => imgui.set_key(4, pressed),
-                            Some(Key::PageUp) => imgui.set_key(5, pressed),
-                            Some(Key::PageDown) => imgui.set_key(6, pressed),
-                            Some(Key::Home) => imgui.set_key(7, pressed),
-                            Some(Key::End) => imgui.set_key(8, pressed),
-                            Some(Key::Delete) => imgui.set_key(9, pressed),
-                            Some(Key::Back) => imgui.set_key(10, pressed),
-                            Some(Key::Return) => imgui.set_key(11, pressed),
-                            Some(Key::Escape) => imgui.set_key(12, pressed),
-                            Some(Key::A) => imgui.set_key(13, pressed),
-                            Some(Key::C) => imgui.set_key(14, pressed),
-                            Some(Key::V) => imgui.set_key(15, pressed),
-                            Some(Key::X) => imgui.set_key(16, pressed),
-                            Some(Key::Y) => imgui.set_key(17, pressed),
-                            Some(Key::Z) => imgui.set_key(18, pressed),
-                            Some(Key::LControl) | Some(Key::RControl) => {
-                                imgui.set_key_ctrl(pressed)
-                            }
-                            Some(Key::LShift) | Some(Key::RShift) => imgui.set_key_shift(pressed),
-                            Some(Key::LAlt) | Some(Key::RAlt) => imgui.set_key_alt(pressed),
-                            Some(Key::LWin) | Some(Key::RWin) => imgui.set_key_super(pressed),
-                            _ => {}
-                        }
-                    }
-                    WindowEvent::CursorMoved {
-                        position: LogicalPosition { x, y },
-                        ..
-                    } => {
-                        if x as i32 != 0 && y as i32 != 0 {
-                            new_absolute_mouse_pos = Some((x as i32, y as i32));
-                        }
-                    }
-                    WindowEvent::MouseInput { state, button, .. } => match button {
-                        MouseButton::Left => {
-                            s.mouse_state.pressed.0 = state == ElementState::Pressed
-                        }
-                        MouseButton::Right => {
-                            s.mouse_state.pressed.1 = state == ElementState::Pressed
-                        }
-                        MouseButton::Middle => {
-                            s.mouse_state.pressed.2 = state == ElementState::Pressed
-                        }
-                        _ => {}
-                    },
-                    WindowEvent::MouseWheel {
-                        delta: MouseScrollDelta::LineDelta(_, y),
-                        phase: TouchPhase::Moved,
-                        ..
-                    } => {
-                        s.mouse_state.wheel = y;
-                    }
-                    WindowEvent::MouseWheel {
-                        delta: MouseScrollDelta::PixelDelta(LogicalPosition { y, .. }),
-                        phase: TouchPhase::Moved,
-                        ..
-                    } => {
-                        s.mouse_state.wheel = y as f32;
-                    }
-                    WindowEvent::ReceivedCharacter(c) => imgui.add_input_character(c),
-                    _ => (),
-                },
-                _ => (),
-            }
+        events_loop.poll_events(|event| {
+            handle_event(event, &mut s, &mut imgui, &display, &mut new_absolute_mouse_pos);
         });
 
         if let Some(pos) = new_absolute_mouse_pos {
@@ -847,6 +2954,8 @@ fn main() {
         let gl_window = display.gl_window();
         let size_pixels = gl_window.get_inner_size().unwrap();
 
+        s.density.upload(&display, renderer.textures());
+
         {
             let ui = imgui.frame(
                 FrameSize::new(
@@ -868,8 +2977,14 @@ fn main() {
             break;
         }
 
-        while time::precise_time_s() - begin_frame < 1.0 / 120.0 {
-            thread::yield_now();
+        s.redraw_frames = s.redraw_frames.saturating_sub(1);
+
+        if s.redraw_frames > 0 || mouse_pressed(&s) {
+            let frame_time = time::precise_time_s() - begin_frame;
+
+            if frame_time < 1.0 / 120.0 {
+                thread::sleep(Duration::from_millis(((1.0 / 120.0 - frame_time) * 1000.0) as u64));
+            }
         }
     }
 }
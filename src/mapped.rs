@@ -0,0 +1,269 @@
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use memmap::Mmap;
+use std::cmp::min;
+use std::fs::{File, Metadata};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::UNIX_EPOCH;
+
+use binary::Format;
+use notify::Notifications;
+use timer::Timer;
+use {merge_range, LoadReport, Lookup, Waker};
+
+/// Samples summarized by one min/max index entry.
+const GROUP: usize = 4096;
+
+const INDEX_MAGIC: &[u8; 8] = b"PLOTMM02";
+
+/// One channel of a memory mapped binary file. Samples are read straight from
+/// the mapping, so only the parts that are looked at are paged in.
+pub struct Mapped {
+    file: Arc<Mmap>,
+    format: Format,
+    channel: usize,
+    count: usize,
+
+    /// Modification time of the file when it was mapped, part of the index key.
+    modified: u64,
+
+    /// Min and max of every GROUP samples. Samples become visible as the index
+    /// covers them.
+    index: Mutex<Vec<(f64, f64)>>,
+}
+
+impl Mapped {
+    fn offset(&self, i: usize) -> usize {
+        self.format.header + i * self.format.stride + self.channel * self.format.sample_type.size()
+    }
+
+    fn scan(&self, start: usize, end: usize) -> Option<(f64, f64)> {
+        (start..end).fold(None, |range, i| {
+            let value = self.sample(i);
+
+            if value.is_nan() {
+                range
+            } else {
+                merge_range(range, Some((value, value)))
+            }
+        })
+    }
+}
+
+impl Lookup for Mapped {
+    fn lookup(&self, x: f64, _zoom: f64) -> Option<f64> {
+        if x >= 0.0 && (x as usize) < self.sample_count() {
+            Some(self.sample(x as usize))
+        } else {
+            None
+        }
+    }
+
+    fn min_max(&self, start: f64, end: f64) -> Option<(f64, f64)> {
+        let count = self.sample_count();
+        let start = min(start.max(0.0) as usize, count);
+        let end = min(end.max(0.0).ceil() as usize, count);
+
+        let first = (start + GROUP - 1) / GROUP;
+        let last = end / GROUP;
+
+        if first >= last {
+            return self.scan(start, end);
+        }
+
+        let range = self.index.lock().unwrap()[first..last]
+            .iter()
+            .filter(|&&(low, high)| low <= high)
+            .fold(None, |range, &group| merge_range(range, Some(group)));
+
+        let range = merge_range(range, self.scan(start, first * GROUP));
+        merge_range(range, self.scan(last * GROUP, end))
+    }
+
+    fn sample_count(&self) -> usize {
+        min(self.count, self.index.lock().unwrap().len() * GROUP)
+    }
+
+    fn sample(&self, i: usize) -> f64 {
+        let offset = self.offset(i);
+        let bytes = &self.file[offset..offset + self.format.sample_type.size()];
+
+        self.format.sample_type.read(bytes, self.format.big_endian)
+    }
+}
+
+/// Maps the file and reads its min/max index if there is an up to date one.
+pub fn open(
+    path: &str,
+    format: &Format,
+    notifications: &Mutex<Notifications>,
+) -> io::Result<Vec<Arc<Mapped>>> {
+    let file = File::open(path)?;
+    let metadata = file.metadata()?;
+    let length = metadata.len() as usize;
+    let modified = modified(&metadata);
+    let mmap = Arc::new(unsafe { Mmap::map(&file)? });
+
+    let count = length.saturating_sub(format.header) / format.stride;
+
+    let channels = (0..format.channels)
+        .map(|channel| {
+            Arc::new(Mapped {
+                file: mmap.clone(),
+                format: format.clone(),
+                channel: channel,
+                count: count,
+                modified: modified,
+                index: Mutex::new(Vec::new()),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    if let Err(e) = load_index(path, length as u64, modified, format, &channels) {
+        if e.kind() != io::ErrorKind::NotFound {
+            notifications.lock().unwrap().info(path, &format!("rebuilding the index, {}", e));
+        }
+
+        for channel in &channels {
+            channel.index.lock().unwrap().clear();
+        }
+    }
+
+    Ok(channels)
+}
+
+/// Builds the part of the index that is missing and saves it next to the file.
+/// Progress is reported as the part of the file that is indexed.
+pub fn index(
+    path: &str,
+    channels: &[Arc<Mapped>],
+    stop_loading: &AtomicBool,
+    waker: &Waker,
+    report: &Arc<Mutex<Option<LoadReport>>>,
+) -> io::Result<()> {
+    let (count, mut done) = match channels.first() {
+        Some(channel) => (channel.count, channel.index.lock().unwrap().len()),
+        None => return Ok(()),
+    };
+    let groups = (count + GROUP - 1) / GROUP;
+
+    let timer = Timer::new();
+    let total = channels[0].file.len() as u64;
+
+    // The index was read from the saved one
+    if done >= groups {
+        *report.lock().unwrap() = Some(LoadReport {
+            bytes: total,
+            total: total,
+            seconds: 0.0,
+        });
+        return Ok(());
+    }
+
+    while done < groups {
+        if stop_loading.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        let start = done * GROUP;
+        let end = min(start + GROUP, count);
+
+        for channel in channels {
+            let range = channel
+                .scan(start, end)
+                .unwrap_or((::std::f64::INFINITY, ::std::f64::NEG_INFINITY));
+            channel.index.lock().unwrap().push(range);
+        }
+
+        done += 1;
+        waker.wake();
+
+        *report.lock().unwrap() = Some(LoadReport {
+            bytes: (channels[0].offset(end) as u64).min(total),
+            total: total,
+            seconds: timer.elapsed(),
+        });
+    }
+
+    save_index(path, channels)
+}
+
+fn index_path(path: &str) -> String {
+    format!("{}.minmax", path)
+}
+
+/// Nanoseconds since the epoch, or 0 where the platform has no modification time.
+fn modified(metadata: &Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |time| time.as_secs() * 1_000_000_000 + time.subsec_nanos() as u64)
+}
+
+fn write_key<W: Write>(writer: &mut W, length: u64, modified: u64, format: &Format) -> io::Result<()> {
+    writer.write_all(INDEX_MAGIC)?;
+    writer.write_u64::<LittleEndian>(length)?;
+    writer.write_u64::<LittleEndian>(modified)?;
+    writer.write_u64::<LittleEndian>(format.header as u64)?;
+    writer.write_u8(format.sample_type as u8)?;
+    writer.write_u8(format.big_endian as u8)?;
+    writer.write_u64::<LittleEndian>(format.channels as u64)?;
+    writer.write_u64::<LittleEndian>(format.stride as u64)?;
+    writer.write_u64::<LittleEndian>(GROUP as u64)
+}
+
+fn save_index(path: &str, channels: &[Arc<Mapped>]) -> io::Result<()> {
+    let first = match channels.first() {
+        Some(channel) => channel,
+        None => return Ok(()),
+    };
+
+    let mut writer = BufWriter::new(File::create(index_path(path))?);
+    write_key(&mut writer, first.file.len() as u64, first.modified, &first.format)?;
+
+    for channel in channels {
+        for &(low, high) in channel.index.lock().unwrap().iter() {
+            writer.write_f64::<LittleEndian>(low)?;
+            writer.write_f64::<LittleEndian>(high)?;
+        }
+    }
+
+    writer.flush()
+}
+
+/// Fails unless the index was written for this file length, modification
+/// time and layout.
+fn load_index(
+    path: &str,
+    length: u64,
+    modified: u64,
+    format: &Format,
+    channels: &[Arc<Mapped>],
+) -> io::Result<()> {
+    let mut reader = BufReader::new(File::open(index_path(path))?);
+
+    let mut expected = Vec::new();
+    write_key(&mut expected, length, modified, format)?;
+
+    let mut key = vec![0; expected.len()];
+    reader.read_exact(&mut key)?;
+
+    if key != expected {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Index is out of date"));
+    }
+
+    for channel in channels {
+        let groups = (channel.count + GROUP - 1) / GROUP;
+        let mut index = channel.index.lock().unwrap();
+
+        for _ in 0..groups {
+            let low = reader.read_f64::<LittleEndian>()?;
+            let high = reader.read_f64::<LittleEndian>()?;
+            index.push((low, high));
+        }
+    }
+
+    Ok(())
+}
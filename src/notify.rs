@@ -0,0 +1,173 @@
+use imgui::{ImGuiCond, ImString, Ui};
+use std::fmt::Display;
+
+/// Bad lines kept as examples in a warning.
+const SAMPLE_LINES: usize = 5;
+
+/// Characters of a bad line that are shown.
+const SAMPLE_LENGTH: usize = 60;
+
+/// Lines that could not be parsed while loading. All of them are counted, but
+/// only the first few are kept.
+#[derive(Debug, Clone, Default)]
+pub struct BadLines {
+    pub count: usize,
+
+    /// Line numbers, counting from 1, with the line.
+    pub samples: Vec<(usize, String)>,
+}
+
+impl BadLines {
+    pub fn new() -> BadLines {
+        BadLines::default()
+    }
+
+    pub fn add(&mut self, number: usize, line: &[u8]) {
+        self.count += 1;
+
+        if self.samples.len() < SAMPLE_LINES {
+            let line = String::from_utf8_lossy(line);
+            self.samples.push((number, line.chars().take(SAMPLE_LENGTH).collect()));
+        }
+    }
+
+    /// Adds bad lines found in a part of the file that starts after offset
+    /// lines.
+    pub fn merge(&mut self, other: BadLines, offset: usize) {
+        self.count += other.count;
+
+        for (number, line) in other.samples {
+            if self.samples.len() < SAMPLE_LINES {
+                self.samples.push((number + offset, line));
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Level {
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub level: Level,
+    pub message: String,
+    pub details: Vec<String>,
+}
+
+/// Errors and warnings from loading, shown until they are dismissed.
+#[derive(Debug, Default)]
+pub struct Notifications {
+    list: Vec<Notification>,
+}
+
+impl Notifications {
+    pub fn new() -> Notifications {
+        Notifications::default()
+    }
+
+    pub fn info(&mut self, source: &str, message: &str) {
+        self.list.push(Notification {
+            level: Level::Info,
+            message: format!("{}: {}", source, message),
+            details: Vec::new(),
+        });
+    }
+
+    pub fn warning(&mut self, source: &str, message: &str) {
+        self.list.push(Notification {
+            level: Level::Warning,
+            message: format!("{}: {}", source, message),
+            details: Vec::new(),
+        });
+    }
+
+    pub fn error<E: Display>(&mut self, source: &str, error: E) {
+        self.list.push(Notification {
+            level: Level::Error,
+            message: format!("{}: {}", source, error),
+            details: Vec::new(),
+        });
+    }
+
+    /// Warns about the bad lines of a file, if there are any.
+    pub fn bad_lines(&mut self, source: &str, bad_lines: &BadLines) {
+        if bad_lines.is_empty() {
+            return;
+        }
+
+        let mut details = bad_lines
+            .samples
+            .iter()
+            .map(|&(number, ref line)| format!("Line {}: {}", number, line))
+            .collect::<Vec<_>>();
+
+        if bad_lines.count > bad_lines.samples.len() {
+            details.push(format!("and {} more", bad_lines.count - bad_lines.samples.len()));
+        }
+
+        self.list.push(Notification {
+            level: Level::Warning,
+            message: format!("{}: skipped {} lines that are not numbers", source, bad_lines.count),
+            details: details,
+        });
+    }
+
+    /// Shows the notifications in the bottom left corner of the window.
+    pub fn window(&mut self, ui: &Ui, window_size: (f32, f32)) {
+        if self.list.is_empty() {
+            return;
+        }
+
+        let mut dismissed = None;
+
+        ui.window(im_str!("Notifications"))
+            .size((420.0, 160.0), ImGuiCond::FirstUseEver)
+            .position((10.0, window_size.1 - 170.0), ImGuiCond::FirstUseEver)
+            .collapsible(false)
+            .build(|| {
+                for (i, notification) in self.list.iter().enumerate() {
+                    ui.with_id(i as i32, || {
+                        if ui.small_button(im_str!("x")) {
+                            dismissed = Some(i);
+                        }
+                        ui.same_line(0.0);
+
+                        let color = match notification.level {
+                            Level::Info => (0.8, 0.8, 0.8, 1.0),
+                            Level::Warning => (1.0, 0.8, 0.2, 1.0),
+                            Level::Error => (1.0, 0.35, 0.35, 1.0),
+                        };
+                        ui.text_colored(color, &ImString::new(notification.message.clone()));
+
+                        if !notification.details.is_empty() {
+                            ui.tree_node(im_str!("Details")).build(|| {
+                                for line in &notification.details {
+                                    ui.text(line);
+                                }
+                            });
+                        }
+                    });
+                }
+
+                if self.list.len() > 1 && ui.button(im_str!("Dismiss all"), (0.0, 0.0)) {
+                    dismissed = Some(::std::usize::MAX);
+                }
+            });
+
+        match dismissed {
+            Some(::std::usize::MAX) => self.list.clear(),
+            Some(i) => {
+                self.list.remove(i);
+            }
+            None => (),
+        }
+    }
+}
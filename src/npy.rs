@@ -0,0 +1,312 @@
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use imgui::{ImStr, Ui};
+use std::cmp::min;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use zip::ZipArchive;
+
+use binary::SampleType;
+use text::append;
+use export;
+use {Block, LoadReport, ProgressReader, Waker};
+
+const MAGIC: &[u8; 6] = b"\x93NUMPY";
+
+/// Values converted per lock of the block lists.
+const CHUNK_VALUES: usize = 64 * 1024;
+
+/// A 1-D or 2-D array in a .npy file or a .npz archive.
+#[derive(Debug, Clone)]
+pub struct Array {
+    /// Name in the .npz archive, or the file name of a .npy file.
+    pub name: String,
+    pub columns: usize,
+    rows: usize,
+    sample_type: SampleType,
+    big_endian: bool,
+    fortran_order: bool,
+
+    /// Entry in the .npz archive.
+    entry: Option<String>,
+}
+
+impl Array {
+    /// One name per column.
+    pub fn channel_names(&self) -> Vec<String> {
+        if self.columns == 1 {
+            vec![self.name.clone()]
+        } else {
+            (0..self.columns).map(|i| format!("{}[{}]", self.name, i)).collect()
+        }
+    }
+}
+
+/// Whether the file starts like a zip archive, in which case it is a .npz.
+fn is_archive(path: &str) -> io::Result<bool> {
+    let mut magic = [0; 4];
+    File::open(path)?.read_exact(&mut magic)?;
+
+    Ok(&magic == b"PK\x03\x04")
+}
+
+pub fn is_npy(path: &str) -> bool {
+    let path = path.to_lowercase();
+    path.ends_with(".npy") || path.ends_with(".npz")
+}
+
+/// Reads the array headers of a .npy or .npz file.
+pub fn arrays(path: &str) -> io::Result<Vec<Array>> {
+    if is_archive(path)? {
+        let mut archive = ZipArchive::new(File::open(path)?)?;
+        let mut arrays = Vec::new();
+
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i)?;
+            let entry_name = entry.name().to_owned();
+
+            if !entry_name.ends_with(".npy") {
+                continue;
+            }
+
+            let mut array = read_header(&mut entry)?;
+            array.name = entry_name.trim_end_matches(".npy").to_owned();
+            array.entry = Some(entry_name);
+            arrays.push(array);
+        }
+
+        Ok(arrays)
+    } else {
+        let mut array = read_header(&mut BufReader::new(File::open(path)?))?;
+        array.name = ::std::path::Path::new(path)
+            .file_stem()
+            .map_or("Ch".to_owned(), |stem| stem.to_string_lossy().into_owned());
+
+        Ok(vec![array])
+    }
+}
+
+/// Loads every column of the arrays into its own block list, in the order of
+/// Array::channel_names.
+pub fn load(
+    path: &str,
+    arrays: &[Array],
+    channels: &[Arc<Mutex<Vec<Box<Block>>>>],
+    stop_loading: &AtomicBool,
+    waker: &Waker,
+    report: &Arc<Mutex<Option<LoadReport>>>,
+) -> io::Result<()> {
+    let mut channels = channels;
+
+    if is_archive(path)? {
+        let mut archive = ZipArchive::new(ProgressReader::open(path, report)?)?;
+
+        for array in arrays {
+            let (targets, rest) = channels.split_at(array.columns);
+            channels = rest;
+
+            let name = array.entry.as_ref().map_or("", |name| name.as_str());
+            let mut entry = archive.by_name(name)?;
+            read_header(&mut entry)?;
+            read_values(&mut entry, array, targets, stop_loading, waker)?;
+        }
+    } else if let Some(array) = arrays.first() {
+        let mut reader = BufReader::new(ProgressReader::open(path, report)?);
+        read_header(&mut reader)?;
+        read_values(&mut reader, array, channels, stop_loading, waker)?;
+    }
+
+    Ok(())
+}
+
+fn read_values<R: Read>(
+    reader: &mut R,
+    array: &Array,
+    channels: &[Arc<Mutex<Vec<Box<Block>>>>],
+    stop_loading: &AtomicBool,
+    waker: &Waker,
+) -> io::Result<()> {
+    let size = array.sample_type.size();
+    let total = array.rows * array.columns;
+    let mut buffer = vec![0; CHUNK_VALUES * size];
+    let mut done = 0;
+
+    while done < total {
+        if stop_loading.load(Ordering::SeqCst) {
+            break;
+        }
+
+        let count = min(CHUNK_VALUES, total - done);
+        let bytes = &mut buffer[..count * size];
+        reader.read_exact(bytes)?;
+
+        let mut values = vec![Vec::new(); array.columns];
+
+        // C order arrays are stored row by row, Fortran order column by column
+        for (k, sample) in bytes.chunks(size).enumerate() {
+            let i = done + k;
+            let column = if array.fortran_order {
+                i / array.rows
+            } else {
+                i % array.columns
+            };
+
+            values[column].push(array.sample_type.read(sample, array.big_endian));
+        }
+
+        for (values, target) in values.iter().zip(channels.iter()) {
+            if !values.is_empty() {
+                append(&mut target.lock().unwrap(), values);
+            }
+        }
+
+        done += count;
+        waker.wake();
+    }
+
+    Ok(())
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+fn read_header<R: Read>(reader: &mut R) -> io::Result<Array> {
+    let mut magic = [0; 6];
+    reader.read_exact(&mut magic)?;
+
+    if &magic != MAGIC {
+        return Err(invalid("Not a NumPy array"));
+    }
+
+    let major = reader.read_u8()?;
+    let _minor = reader.read_u8()?;
+
+    let length = if major == 1 {
+        reader.read_u16::<LittleEndian>()? as usize
+    } else {
+        reader.read_u32::<LittleEndian>()? as usize
+    };
+
+    let mut header = vec![0; length];
+    reader.read_exact(&mut header)?;
+    let header = String::from_utf8_lossy(&header);
+
+    let descr = dict_value(&header, "descr").ok_or_else(|| invalid("No descr in NumPy header"))?;
+    let fortran_order = dict_value(&header, "fortran_order") == Some("True");
+    let shape = dict_value(&header, "shape").ok_or_else(|| invalid("No shape in NumPy header"))?;
+
+    let (big_endian, kind) = match descr.chars().next() {
+        Some('>') => (true, &descr[1..]),
+        Some('<') | Some('|') | Some('=') => (false, &descr[1..]),
+        _ => (false, descr),
+    };
+
+    let sample_type = match kind {
+        "b1" | "u1" => SampleType::U8,
+        "i1" => SampleType::I8,
+        "i2" => SampleType::I16,
+        "u2" => SampleType::U16,
+        "i4" => SampleType::I32,
+        "u4" => SampleType::U32,
+        "i8" => SampleType::I64,
+        "u8" => SampleType::U64,
+        "f4" => SampleType::F32,
+        "f8" => SampleType::F64,
+        _ => return Err(invalid(&format!("Unsupported NumPy dtype {}", descr))),
+    };
+
+    let shape = shape
+        .trim_matches(|c| c == '(' || c == ')')
+        .split(',')
+        .map(|dimension| dimension.trim())
+        .filter(|dimension| !dimension.is_empty())
+        .map(|dimension| dimension.parse::<usize>().map_err(|_| invalid("Bad NumPy shape")))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let (rows, columns) = match shape.len() {
+        0 => (1, 1),
+        1 => (shape[0], 1),
+        2 => (shape[0], shape[1]),
+        _ => return Err(invalid("Only 1-D and 2-D NumPy arrays are supported")),
+    };
+
+    Ok(Array {
+        name: String::new(),
+        columns: columns,
+        rows: rows,
+        sample_type: sample_type,
+        big_endian: big_endian,
+        fortran_order: fortran_order,
+        entry: None,
+    })
+}
+
+/// The value of a key in the header, which is a Python dict literal like
+/// `{'descr': '<f8', 'fortran_order': False, 'shape': (10, 2), }`.
+fn dict_value<'a>(header: &'a str, key: &str) -> Option<&'a str> {
+    let start = header.find(&format!("'{}'", key))? + key.len() + 2;
+    let rest = header[start..].trim_start().trim_start_matches(':').trim_start();
+
+    let end = if rest.starts_with('\'') {
+        return rest[1..].split('\'').next();
+    } else if rest.starts_with('(') {
+        rest.find(')')? + 1
+    } else {
+        rest.find(|c| c == ',' || c == '}')?
+    };
+
+    Some(rest[..end].trim())
+}
+
+/// A .npy export, which has no options.
+#[derive(Debug)]
+pub struct Options;
+
+impl export::Format for Options {
+    type Error = io::Error;
+
+    fn title(&self) -> &'static ImStr {
+        im_str!("Export NumPy")
+    }
+
+    fn extension(&self) -> &'static str {
+        "npy"
+    }
+
+    fn options(&mut self, _ui: &Ui) {}
+
+    /// Writes a float64 array with one column per channel, or a 1-D array for
+    /// a single channel. Channels that end early are padded with NaN.
+    fn write(&self, path: &str, selected: &[Vec<f64>], rows: usize) -> io::Result<()> {
+        let shape = if selected.len() == 1 {
+            format!("({},)", rows)
+        } else {
+            format!("({}, {})", rows, selected.len())
+        };
+
+        // The header is padded so that the data starts 64 byte aligned
+        let mut header = format!("{{'descr': '<f8', 'fortran_order': False, 'shape': {}, }}", shape);
+        while (MAGIC.len() + 4 + header.len() + 1) % 64 != 0 {
+            header.push(' ');
+        }
+        header.push('\n');
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[1, 0])?;
+        writer.write_u16::<LittleEndian>(header.len() as u16)?;
+        writer.write_all(header.as_bytes())?;
+
+        for i in 0..rows {
+            for samples in selected {
+                let value = samples.get(i).cloned().unwrap_or(::std::f64::NAN);
+
+                writer.write_f64::<LittleEndian>(value)?;
+            }
+        }
+
+        writer.flush()
+    }
+}
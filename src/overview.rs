@@ -0,0 +1,92 @@
+use imgui::WindowDrawList;
+
+use Channel;
+
+pub const HEIGHT: f32 = 60.0;
+
+/// How close to an edge of the view rectangle a press resizes it.
+const EDGE_GRAB: f32 = 4.0;
+
+#[derive(Debug, Copy, Clone)]
+pub enum Drag {
+    Move,
+    Left,
+    Right,
+}
+
+/// Horizontal extent of the current view in overview coordinates.
+pub fn view_rect(origin: f64, scale: f64, width: f32, count: usize) -> (f32, f32) {
+    let count = count.max(1) as f64;
+    let left = origin / count * width as f64;
+    let right = (origin + scale * width as f64) / count * width as f64;
+
+    (left as f32, right as f32)
+}
+
+pub fn drag_at(mouse_x: f32, rect: (f32, f32)) -> Option<Drag> {
+    let (left, right) = rect;
+
+    if (mouse_x - left).abs() < EDGE_GRAB {
+        Some(Drag::Left)
+    } else if (mouse_x - right).abs() < EDGE_GRAB {
+        Some(Drag::Right)
+    } else if mouse_x > left && mouse_x < right {
+        Some(Drag::Move)
+    } else {
+        None
+    }
+}
+
+/// Draws every channel over the whole recording, each normalized to the strip
+/// height, with the current view highlighted.
+pub fn draw(
+    draw_list: &WindowDrawList,
+    channels: &[Channel],
+    top: f32,
+    width: f32,
+    count: usize,
+    rect: (f32, f32),
+) {
+    let bottom = top + HEIGHT;
+
+    draw_list
+        .add_rect((0.0, top), (width, bottom), 0xf0202020)
+        .filled(true)
+        .build();
+
+    if count > 0 {
+        let samples_per_column = count as f64 / width as f64;
+
+        for channel in channels {
+            let blocks = channel.samples();
+
+            let (min, max) = match channel.coarse_min_max(&*blocks, 0.0, count as f64) {
+                Some(range) => range,
+                None => continue,
+            };
+            let span = if max - min > 1e-9 { max - min } else { 1.0 };
+            let to_y = |value: f64| bottom - 2.0 - ((value - min) / span) as f32 * (HEIGHT - 4.0);
+
+            for x in 0..(width as i32) {
+                let start = x as f64 * samples_per_column;
+                let end = start + samples_per_column.max(1.0);
+
+                if let Some((low, high)) = channel.coarse_min_max(&*blocks, start, end) {
+                    draw_list
+                        .add_line((x as f32, to_y(low)), (x as f32, to_y(high) - 1.0), channel.packed_color())
+                        .build();
+                }
+            }
+        }
+    }
+
+    let (left, right) = rect;
+
+    draw_list
+        .add_rect((left, top), (right.max(left + 1.0), bottom), 0x30ffffff)
+        .filled(true)
+        .build();
+    draw_list
+        .add_rect((left, top), (right.max(left + 1.0), bottom), 0xa0ffffff)
+        .build();
+}
@@ -0,0 +1,201 @@
+use imgui::{ImStr, ImString, Ui};
+use std::cmp::max;
+use time;
+
+use {Channel, Lookup};
+
+/// How long auto mode waits for a trigger before it starts a free running sweep.
+const AUTO_TIMEOUT: f64 = 0.1;
+
+/// Upper bound on how many samples are searched for a trigger every frame.
+const MAX_SEARCH: usize = 1_000_000;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Slope {
+    Rising,
+    Falling,
+    Either,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TriggerMode {
+    Auto,
+    Normal,
+    Single,
+}
+
+#[derive(Debug)]
+pub struct Scope {
+    pub enabled: bool,
+    pub channel: usize,
+    pub level: f32,
+    pub slope: Slope,
+    pub mode: TriggerMode,
+
+    /// Part of the sweep shown before the trigger, in percent.
+    pub pre_trigger: f32,
+
+    /// Minimum number of samples between two triggers.
+    pub holdoff: f32,
+
+    /// Number of sweeps triggered since the scope was enabled.
+    pub sweeps: usize,
+
+    armed: bool,
+    trigger: Option<usize>,
+    last_trigger_time: f64,
+}
+
+impl Scope {
+    pub fn new() -> Scope {
+        Scope {
+            enabled: false,
+            channel: 0,
+            level: 0.0,
+            slope: Slope::Rising,
+            mode: TriggerMode::Auto,
+            pre_trigger: 10.0,
+            holdoff: 0.0,
+            sweeps: 0,
+            armed: true,
+            trigger: None,
+            last_trigger_time: 0.0,
+        }
+    }
+
+    pub fn arm(&mut self) {
+        self.armed = true;
+    }
+
+    /// Sample index of the current trigger.
+    pub fn trigger(&self) -> Option<usize> {
+        self.trigger
+    }
+
+    /// Sample index of the left edge of the current sweep.
+    pub fn origin(&self, sweep_length: f64) -> Option<f64> {
+        let pre = sweep_length * self.pre_trigger as f64 / 100.0;
+        self.trigger.map(|trigger| trigger as f64 - pre)
+    }
+
+    /// Looks for a new trigger in blocks, which should be the trigger channel.
+    pub fn update(&mut self, blocks: &dyn Lookup, sweep_length: f64) {
+        let count = blocks.sample_count();
+        let now = time::precise_time_s();
+
+        if self.trigger.map_or(false, |trigger| trigger > count) {
+            self.trigger = None;
+        }
+
+        if self.mode == TriggerMode::Single && !self.armed {
+            return;
+        }
+
+        let post = (sweep_length * (1.0 - self.pre_trigger as f64 / 100.0)).ceil() as usize;
+
+        let latest = count.saturating_sub(post);
+        let earliest = match self.trigger {
+            Some(trigger) => trigger + max(self.holdoff as usize, 1),
+            None => 0,
+        };
+        let earliest = max(earliest, latest.saturating_sub(MAX_SEARCH));
+
+        if let Some(trigger) = find_trigger(blocks, earliest, latest, self.level as f64, self.slope) {
+            self.trigger = Some(trigger);
+            self.last_trigger_time = now;
+            self.sweeps += 1;
+            self.armed = false;
+        } else if self.mode == TriggerMode::Auto && now - self.last_trigger_time > AUTO_TIMEOUT {
+            self.trigger = Some(latest);
+            self.last_trigger_time = now;
+            self.sweeps += 1;
+        }
+    }
+
+    pub fn properties(&mut self, ui: &Ui, channels: &[Channel]) {
+        ui.checkbox(im_str!("Scope mode"), &mut self.enabled);
+
+        let names = channels
+            .iter()
+            .map(|channel| ImString::new(channel.name.clone()))
+            .collect::<Vec<_>>();
+        let items = names.iter().map(|name| &**name).collect::<Vec<&ImStr>>();
+
+        let mut channel = self.channel as i32;
+        if ui.combo(im_str!("Trigger channel"), &mut channel, &items, 5) {
+            self.channel = channel as usize;
+            self.trigger = None;
+        }
+
+        ui.drag_float(im_str!("Trigger level"), &mut self.level)
+            .speed(0.1)
+            .build();
+
+        let mut slope = self.slope as i32;
+        if ui.combo(
+            im_str!("Slope"),
+            &mut slope,
+            &[im_str!("Rising"), im_str!("Falling"), im_str!("Either")],
+            3,
+        ) {
+            self.slope = match slope {
+                0 => Slope::Rising,
+                1 => Slope::Falling,
+                _ => Slope::Either,
+            };
+        }
+
+        let mut mode = self.mode as i32;
+        if ui.combo(
+            im_str!("Trigger mode"),
+            &mut mode,
+            &[im_str!("Auto"), im_str!("Normal"), im_str!("Single")],
+            3,
+        ) {
+            self.mode = match mode {
+                0 => TriggerMode::Auto,
+                1 => TriggerMode::Normal,
+                _ => TriggerMode::Single,
+            };
+        }
+
+        ui.slider_float(im_str!("Pre-trigger %"), &mut self.pre_trigger, 0.0, 100.0)
+            .build();
+
+        if ui.drag_float(im_str!("Holdoff"), &mut self.holdoff)
+            .speed(1.0)
+            .min(0.0)
+            .build()
+        {
+            self.holdoff = self.holdoff.max(0.0);
+        }
+
+        if self.mode == TriggerMode::Single {
+            if ui.button(im_str!("Arm"), (0.0, 0.0)) {
+                self.arm();
+            }
+            ui.same_line(0.0);
+            ui.text(if self.armed { "Armed" } else { "Stopped" });
+        }
+
+        ui.text(im_str!("Sweeps: {}", self.sweeps));
+    }
+}
+
+/// Index of the last sample in [from, to) where the signal crosses level.
+fn find_trigger(blocks: &dyn Lookup, from: usize, to: usize, level: f64, slope: Slope) -> Option<usize> {
+    let sample = |i: usize| blocks.sample(i);
+
+    (max(from, 1)..to).rev().find(|&i| {
+        let (last, current) = (sample(i - 1), sample(i));
+
+        let rising = last < level && current >= level;
+        let falling = last > level && current <= level;
+
+        match slope {
+            Slope::Rising => rising,
+            Slope::Falling => falling,
+            Slope::Either => rising || falling,
+        }
+    })
+}
@@ -0,0 +1,250 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+
+use {Channel, DrawStyle, Layout, State};
+
+/// Everything about a channel that is saved with the session.
+#[derive(Debug, Clone)]
+pub struct ChannelProperties {
+    pub name: String,
+    pub color: [f32; 4],
+    pub line_width: f32,
+    pub visible: bool,
+    pub solo: bool,
+    pub style: DrawStyle,
+    pub digital: bool,
+    pub pan: f32,
+    pub scale: f32,
+    pub lane_weight: f32,
+}
+
+impl ChannelProperties {
+    pub fn of(channel: &Channel) -> ChannelProperties {
+        ChannelProperties {
+            name: channel.name.clone(),
+            color: channel.color,
+            line_width: channel.line_width,
+            visible: channel.visible,
+            solo: channel.solo,
+            style: channel.style,
+            digital: channel.digital,
+            pan: channel.pan,
+            scale: channel.scale,
+            lane_weight: channel.lane_weight,
+        }
+    }
+
+    pub fn apply(&self, channel: &mut Channel) {
+        channel.name = self.name.clone();
+        channel.color = self.color;
+        channel.line_width = self.line_width;
+        channel.visible = self.visible;
+        channel.solo = self.solo;
+        channel.style = self.style;
+        channel.digital = self.digital;
+        channel.pan = self.pan;
+        channel.scale = self.scale;
+        channel.lane_weight = self.lane_weight;
+    }
+}
+
+/// View settings and channel properties, stored as a plain text file with one
+/// `key = value` pair per line. Each `[channel]` line starts a new channel.
+#[derive(Debug)]
+pub struct Session {
+    pub layout: Layout,
+    pub pan: (f64, f64),
+    pub scroll_factor: f64,
+    pub window_y_scale: f32,
+    pub channels: Vec<ChannelProperties>,
+}
+
+impl Session {
+    pub fn capture(state: &State) -> Session {
+        Session {
+            layout: state.layout,
+            pan: state.pan,
+            scroll_factor: state.scroll_factor,
+            window_y_scale: state.window_y_scale,
+            channels: state
+                .data
+                .channels
+                .iter()
+                .map(ChannelProperties::of)
+                .collect(),
+        }
+    }
+
+    /// Applies the view and the channel properties to the open channels. When
+    /// sources from the command line are still queued, their channels get the
+    /// properties as they are loaded.
+    pub fn apply(&self, state: &mut State) {
+        state.layout = self.layout;
+        state.pan = self.pan;
+        state.scroll_factor = self.scroll_factor;
+        state.window_y_scale = self.window_y_scale;
+
+        if !state.open_queue.is_empty() {
+            state.data.properties = self.channels.clone();
+        }
+
+        for (properties, channel) in self.channels.iter().zip(state.data.channels.iter_mut()) {
+            properties.apply(channel);
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = File::create(path)?;
+
+        writeln!(file, "layout = {}", layout_name(self.layout))?;
+        writeln!(file, "pan = {} {}", self.pan.0, self.pan.1)?;
+        writeln!(file, "scroll_factor = {}", self.scroll_factor)?;
+        writeln!(file, "window_y_scale = {}", self.window_y_scale)?;
+
+        for channel in &self.channels {
+            writeln!(file)?;
+            writeln!(file, "[channel]")?;
+            writeln!(file, "name = {}", channel.name)?;
+            writeln!(
+                file,
+                "color = {} {} {} {}",
+                channel.color[0], channel.color[1], channel.color[2], channel.color[3]
+            )?;
+            writeln!(file, "line_width = {}", channel.line_width)?;
+            writeln!(file, "visible = {}", channel.visible)?;
+            writeln!(file, "solo = {}", channel.solo)?;
+            writeln!(file, "style = {}", style_name(channel.style))?;
+            writeln!(file, "digital = {}", channel.digital)?;
+            writeln!(file, "pan = {}", channel.pan)?;
+            writeln!(file, "scale = {}", channel.scale)?;
+            writeln!(file, "lane_weight = {}", channel.lane_weight)?;
+        }
+
+        Ok(())
+    }
+
+    /// Unknown keys and malformed values are ignored and keep their defaults.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Session> {
+        let reader = BufReader::new(File::open(path)?);
+
+        let mut session = Session {
+            layout: Layout::Overlay,
+            pan: (0.0, 0.0),
+            scroll_factor: 0.0,
+            window_y_scale: 1.0,
+            channels: Vec::new(),
+        };
+
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+
+            if line == "[channel]" {
+                let index = session.channels.len();
+                session.channels.push(ChannelProperties::of(&Channel::new(
+                    &format!("Ch {}", index),
+                    0xffffffff,
+                )));
+                continue;
+            }
+
+            let (key, value) = match line.find('=') {
+                Some(i) => (line[..i].trim(), line[i + 1..].trim()),
+                None => continue,
+            };
+
+            match session.channels.last_mut() {
+                Some(channel) => match key {
+                    "name" => channel.name = value.to_owned(),
+                    "color" => {
+                        let parts = parse_floats::<f32>(value);
+                        if parts.len() == 4 {
+                            channel.color = [parts[0], parts[1], parts[2], parts[3]];
+                        }
+                    }
+                    "line_width" => parse_into(value, &mut channel.line_width),
+                    "visible" => parse_into(value, &mut channel.visible),
+                    "solo" => parse_into(value, &mut channel.solo),
+                    "style" => {
+                        if let Some(style) = parse_style(value) {
+                            channel.style = style;
+                        }
+                    }
+                    "digital" => parse_into(value, &mut channel.digital),
+                    "pan" => parse_into(value, &mut channel.pan),
+                    "scale" => parse_into(value, &mut channel.scale),
+                    "lane_weight" => parse_into(value, &mut channel.lane_weight),
+                    _ => (),
+                },
+                None => match key {
+                    "layout" => {
+                        if let Some(layout) = parse_layout(value) {
+                            session.layout = layout;
+                        }
+                    }
+                    "pan" => {
+                        let parts = parse_floats::<f64>(value);
+                        if parts.len() == 2 {
+                            session.pan = (parts[0], parts[1]);
+                        }
+                    }
+                    "scroll_factor" => parse_into(value, &mut session.scroll_factor),
+                    "window_y_scale" => parse_into(value, &mut session.window_y_scale),
+                    _ => (),
+                },
+            }
+        }
+
+        Ok(session)
+    }
+}
+
+fn parse_into<T: ::std::str::FromStr>(value: &str, target: &mut T) {
+    if let Ok(value) = value.parse() {
+        *target = value;
+    }
+}
+
+fn parse_floats<T: ::std::str::FromStr>(value: &str) -> Vec<T> {
+    value
+        .split_whitespace()
+        .filter_map(|part| part.parse().ok())
+        .collect()
+}
+
+pub fn layout_name(layout: Layout) -> &'static str {
+    match layout {
+        Layout::Overlay => "overlay",
+        Layout::Stacked => "stacked",
+    }
+}
+
+pub fn parse_layout(value: &str) -> Option<Layout> {
+    match value {
+        "overlay" => Some(Layout::Overlay),
+        "stacked" => Some(Layout::Stacked),
+        _ => None,
+    }
+}
+
+fn style_name(style: DrawStyle) -> &'static str {
+    match style {
+        DrawStyle::Line => "line",
+        DrawStyle::Step => "step",
+        DrawStyle::Points => "points",
+        DrawStyle::Logic => "logic",
+        DrawStyle::Bus => "bus",
+    }
+}
+
+fn parse_style(value: &str) -> Option<DrawStyle> {
+    match value {
+        "line" => Some(DrawStyle::Line),
+        "step" => Some(DrawStyle::Step),
+        "points" => Some(DrawStyle::Points),
+        "logic" => Some(DrawStyle::Logic),
+        "bus" => Some(DrawStyle::Bus),
+        _ => None,
+    }
+}
@@ -0,0 +1,218 @@
+use byteorder::{ByteOrder, LittleEndian};
+use std::fs::File;
+use std::io::{self, Read, Seek};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use zip::ZipArchive;
+
+use text::append;
+use {Block, LoadReport, ProgressReader, Waker};
+
+/// A logic probe or analog channel of the capture.
+#[derive(Debug, Clone)]
+pub struct Probe {
+    pub name: String,
+    pub analog: bool,
+
+    /// Bit of a logic sample, or the number in the analog chunk names.
+    index: usize,
+}
+
+/// The `metadata` of a sigrok session file.
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub probes: Vec<Probe>,
+    pub sample_rate: Option<f64>,
+    capture_file: String,
+    unit_size: usize,
+}
+
+/// Whether the file is a zip archive, which is what .sr files are.
+pub fn is_session(path: &str) -> bool {
+    let mut magic = [0; 4];
+
+    path.to_lowercase().ends_with(".sr")
+        && File::open(path)
+            .and_then(|mut file| file.read_exact(&mut magic))
+            .is_ok()
+        && &magic == b"PK\x03\x04"
+}
+
+/// Reads the probes and sample rate of the first device.
+pub fn read_session(path: &str) -> io::Result<Session> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+
+    let mut metadata = String::new();
+    archive.by_name("metadata")?.read_to_string(&mut metadata)?;
+
+    let mut session = Session {
+        probes: Vec::new(),
+        sample_rate: None,
+        capture_file: "logic-1".to_owned(),
+        unit_size: 1,
+    };
+    let mut section = String::new();
+
+    for line in metadata.lines().map(|line| line.trim()) {
+        if line.starts_with('[') {
+            section = line.trim_matches(|c| c == '[' || c == ']').to_owned();
+            continue;
+        }
+
+        let mut parts = line.splitn(2, '=');
+        let (key, value) = match (parts.next(), parts.next()) {
+            (Some(key), Some(value)) => (key.trim(), value.trim()),
+            _ => continue,
+        };
+
+        if section != "device 1" {
+            continue;
+        }
+
+        if key == "capturefile" {
+            session.capture_file = value.to_owned();
+        } else if key == "unitsize" {
+            session.unit_size = value.parse().unwrap_or(1);
+        } else if key == "samplerate" {
+            session.sample_rate = parse_rate(value);
+        } else if key.starts_with("probe") || key.starts_with("analog") {
+            let analog = key.starts_with("analog");
+            let number = key.trim_start_matches(|c: char| c.is_alphabetic());
+
+            if let Ok(index) = number.parse::<usize>() {
+                session.probes.push(Probe {
+                    name: value.to_owned(),
+                    analog: analog,
+                    index: if analog { index } else { index.saturating_sub(1) },
+                });
+            }
+        }
+    }
+
+    Ok(session)
+}
+
+/// Sample rates are written like `1 MHz`.
+fn parse_rate(rate: &str) -> Option<f64> {
+    let split = rate
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(rate.len());
+    let (number, unit) = rate.split_at(split);
+
+    let unit = match unit.trim() {
+        "" | "Hz" => 1.0,
+        "kHz" => 1e3,
+        "MHz" => 1e6,
+        "GHz" => 1e9,
+        _ => return None,
+    };
+
+    number.parse::<f64>().ok().map(|number| number * unit)
+}
+
+/// Loads the capture, one channel per probe in the order of session.probes.
+/// Logic probes become 0 or 1 and analog channels are read as 32 bit floats.
+pub fn load(
+    path: &str,
+    session: &Session,
+    channels: &[Arc<Mutex<Vec<Box<Block>>>>],
+    stop_loading: &AtomicBool,
+    waker: &Waker,
+    report: &Arc<Mutex<Option<LoadReport>>>,
+) -> io::Result<()> {
+    let mut archive = ZipArchive::new(ProgressReader::open(path, report)?)?;
+
+    let logic = session
+        .probes
+        .iter()
+        .zip(channels.iter())
+        .filter(|&(probe, _)| !probe.analog)
+        .collect::<Vec<_>>();
+
+    if !logic.is_empty() {
+        // Version 1 files have a single chunk named after the capture file,
+        // later ones number their chunks starting from 1
+        let single = archive.by_name(&session.capture_file).is_ok();
+
+        for chunk in 1.. {
+            if stop_loading.load(Ordering::SeqCst) {
+                return Ok(());
+            }
+
+            let name = if single {
+                session.capture_file.clone()
+            } else {
+                format!("{}-{}", session.capture_file, chunk)
+            };
+
+            let bytes = match read_entry(&mut archive, &name)? {
+                Some(bytes) => bytes,
+                None => break,
+            };
+
+            for &(probe, target) in &logic {
+                let byte = probe.index / 8;
+                let mask = 1 << (probe.index % 8);
+
+                if byte >= session.unit_size {
+                    continue;
+                }
+
+                let values = bytes
+                    .chunks(session.unit_size)
+                    .filter(|unit| unit.len() == session.unit_size)
+                    .map(|unit| if unit[byte] & mask != 0 { 1.0 } else { 0.0 })
+                    .collect::<Vec<_>>();
+                append(&mut target.lock().unwrap(), &values);
+            }
+
+            waker.wake();
+
+            if single {
+                break;
+            }
+        }
+    }
+
+    for (probe, target) in session.probes.iter().zip(channels.iter()) {
+        if !probe.analog {
+            continue;
+        }
+
+        for chunk in 1.. {
+            if stop_loading.load(Ordering::SeqCst) {
+                return Ok(());
+            }
+
+            let name = format!("analog-1-{}-{}", probe.index, chunk);
+            let bytes = match read_entry(&mut archive, &name)? {
+                Some(bytes) => bytes,
+                None => break,
+            };
+
+            let values = bytes
+                .chunks(4)
+                .filter(|sample| sample.len() == 4)
+                .map(|sample| LittleEndian::read_f32(sample) as f64)
+                .collect::<Vec<_>>();
+            append(&mut target.lock().unwrap(), &values);
+
+            waker.wake();
+        }
+    }
+
+    Ok(())
+}
+
+/// The contents of an archive entry, or None if there is no such entry.
+fn read_entry<R: Read + Seek>(archive: &mut ZipArchive<R>, name: &str) -> io::Result<Option<Vec<u8>>> {
+    let mut entry = match archive.by_name(name) {
+        Ok(entry) => entry,
+        Err(_) => return Ok(None),
+    };
+
+    let mut bytes = Vec::new();
+    entry.read_to_end(&mut bytes)?;
+
+    Ok(Some(bytes))
+}
@@ -0,0 +1,684 @@
+use flate2::read::MultiGzDecoder;
+use num_cpus;
+use xz2::read::XzDecoder;
+use zstd::stream::read::Decoder as ZstdDecoder;
+use std::collections::BTreeMap;
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use notify::{BadLines, Notifications};
+use timer::Timer;
+use {Block, LoadReport, ProgressReader, Waker};
+
+/// Bytes parsed by one worker at a time.
+const CHUNK_SIZE: u64 = 8 * 1024 * 1024;
+
+/// How often a followed file is checked for new data, in milliseconds.
+const FOLLOW_INTERVAL: u64 = 50;
+
+/// Powers of ten that are exact as f64.
+const POWERS_OF_TEN: [f64; 23] = [
+    1e0, 1e1, 1e2, 1e3, 1e4, 1e5, 1e6, 1e7, 1e8, 1e9, 1e10, 1e11, 1e12, 1e13, 1e14, 1e15, 1e16,
+    1e17, 1e18, 1e19, 1e20, 1e21, 1e22,
+];
+
+/// Loads a file with one value per line. The file is split into chunks on line
+/// boundaries that are parsed on all cores, and the results are appended to
+/// blocks in file order. Lines that are not numbers are skipped and returned.
+///
+/// With follow, loading continues with lines appended to the file until
+/// stop_loading is set.
+pub fn load(
+    path: &str,
+    follow: bool,
+    blocks: &Arc<Mutex<Vec<Box<Block>>>>,
+    stop_loading: &Arc<AtomicBool>,
+    waker: &Waker,
+    report: &Arc<Mutex<Option<LoadReport>>>,
+    notifications: &Mutex<Notifications>,
+) -> io::Result<BadLines> {
+    let timer = Timer::new();
+    let mut file = File::open(path)?;
+    let size = if follow {
+        complete_length(&mut file)?
+    } else {
+        file.metadata()?.len()
+    };
+    let chunks = ((size + CHUNK_SIZE - 1) / CHUNK_SIZE) as usize;
+
+    let next_chunk = Arc::new(AtomicUsize::new(0));
+    let (sender, receiver) = mpsc::channel();
+
+    let workers = (0..num_cpus::get().max(1))
+        .map(|_| {
+            let path = path.to_owned();
+            let next_chunk = next_chunk.clone();
+            let stop_loading = stop_loading.clone();
+            let sender = sender.clone();
+
+            thread::spawn(move || -> io::Result<()> {
+                let mut file = File::open(&path)?;
+
+                loop {
+                    let chunk = next_chunk.fetch_add(1, Ordering::SeqCst);
+
+                    if chunk >= chunks || stop_loading.load(Ordering::SeqCst) {
+                        return Ok(());
+                    }
+
+                    let start = chunk as u64 * CHUNK_SIZE;
+                    let end = (start + CHUNK_SIZE).min(size);
+                    let parsed = parse_chunk(&mut file, start, end, &stop_loading)?;
+
+                    if sender.send((chunk, parsed)).is_err() {
+                        return Ok(());
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    drop(sender);
+
+    let mut pending = BTreeMap::new();
+    let mut next = 0;
+    let mut block = Box::new(Block::new());
+    let mut lines = 0;
+    let mut bad_lines = BadLines::new();
+
+    for (chunk, parsed) in receiver {
+        if stop_loading.load(Ordering::SeqCst) {
+            break;
+        }
+
+        pending.insert(chunk, parsed);
+
+        while let Some(parsed) = pending.remove(&next) {
+            let mut full_blocks = Vec::new();
+
+            bad_lines.merge(parsed.bad_lines, lines);
+            lines += parsed.lines;
+
+            for value in parsed.values {
+                if block.data0.is_full() {
+                    full_blocks.push(::std::mem::replace(&mut block, Box::new(Block::new())));
+                }
+
+                block.push(value);
+            }
+
+            blocks.lock().unwrap().extend(full_blocks);
+            next += 1;
+
+            *report.lock().unwrap() = Some(LoadReport {
+                bytes: (next as u64 * CHUNK_SIZE).min(size),
+                total: size,
+                seconds: timer.elapsed(),
+            });
+
+            waker.wake();
+        }
+    }
+
+    // Keep what has been loaded, also when loading was stopped
+    if block.data0.len() != 0 {
+        blocks.lock().unwrap().push(block);
+    }
+
+    for worker in workers {
+        match worker.join() {
+            Ok(result) => result?,
+            Err(_) => return Err(io::Error::new(io::ErrorKind::Other, "Loader thread panicked")),
+        }
+    }
+
+    if follow && !stop_loading.load(Ordering::SeqCst) {
+        file.seek(SeekFrom::Start(size))?;
+
+        let followed = follow_file(path, file, lines, blocks, stop_loading, waker, notifications)?;
+        bad_lines.merge(followed, 0);
+    }
+
+    Ok(bad_lines)
+}
+
+/// Length of the file up to and including its last newline, so that a line
+/// that is still being written is left for follow_file.
+fn complete_length(file: &mut File) -> io::Result<u64> {
+    let size = file.metadata()?.len();
+    let mut end = size;
+    let mut buffer = vec![0; 64 * 1024];
+
+    while end > 0 {
+        let start = end.saturating_sub(buffer.len() as u64);
+        let buffer = &mut buffer[..(end - start) as usize];
+
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(buffer)?;
+
+        if let Some(i) = buffer.iter().rposition(|&b| b == b'\n') {
+            return Ok(start + i as u64 + 1);
+        }
+
+        end = start;
+    }
+
+    Ok(0)
+}
+
+/// Whether two metadata describe the same file, so that a rotated log is
+/// noticed even when the new file is already longer than the old one.
+#[cfg(unix)]
+fn same_file(a: &Metadata, b: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+#[cfg(not(unix))]
+fn same_file(_a: &Metadata, _b: &Metadata) -> bool {
+    true
+}
+
+/// Appends lines written to the file from its position on, which is after
+/// line number first_line. When the file is truncated or replaced, reading
+/// starts over from its beginning. Returns the lines that are not numbers.
+fn follow_file(
+    path: &str,
+    mut file: File,
+    first_line: usize,
+    blocks: &Arc<Mutex<Vec<Box<Block>>>>,
+    stop_loading: &AtomicBool,
+    waker: &Waker,
+    notifications: &Mutex<Notifications>,
+) -> io::Result<BadLines> {
+    let mut buffer = vec![0; 64 * 1024];
+    let mut lines = Lines::new(first_line);
+    let mut bad_lines = BadLines::new();
+    let mut values = Vec::new();
+    let mut unseen = false;
+    let mut position = file.seek(SeekFrom::Current(0))?;
+
+    while !stop_loading.load(Ordering::SeqCst) {
+        let read = file.read(&mut buffer)?;
+
+        if read == 0 {
+            // Wakes are throttled, make sure the last values are drawn
+            if unseen {
+                waker.wake_now();
+                unseen = false;
+            }
+
+            // The file may be missing for a moment while it is rotated
+            if let Ok(metadata) = fs::metadata(path) {
+                if metadata.len() < position || !same_file(&metadata, &file.metadata()?) {
+                    notifications
+                        .lock()
+                        .unwrap()
+                        .info(path, "truncated or replaced, reading from the start");
+                    waker.wake_now();
+
+                    file = File::open(path)?;
+                    position = 0;
+                    bad_lines.merge(lines.bad_lines, 0);
+                    lines = Lines::new(0);
+                    continue;
+                }
+            }
+
+            thread::sleep(Duration::from_millis(FOLLOW_INTERVAL));
+            continue;
+        }
+
+        position += read as u64;
+        lines.feed(&buffer[..read], &mut values);
+
+        if !values.is_empty() {
+            append(&mut blocks.lock().unwrap(), &values);
+            values.clear();
+            waker.wake();
+            unseen = true;
+        }
+    }
+
+    bad_lines.merge(lines.bad_lines, 0);
+
+    Ok(bad_lines)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+/// Detects compressed files by their magic bytes.
+pub fn compression(path: &str) -> io::Result<Option<Compression>> {
+    let mut magic = Vec::new();
+    File::open(path)?.take(6).read_to_end(&mut magic)?;
+
+    Ok(if magic.starts_with(&[0x1f, 0x8b]) {
+        Some(Compression::Gzip)
+    } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        Some(Compression::Zstd)
+    } else if magic.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+        Some(Compression::Xz)
+    } else {
+        None
+    })
+}
+
+/// Loads a compressed file with one value per line, decompressing it while
+/// reading. Progress is reported against the compressed size.
+pub fn load_compressed(
+    path: &str,
+    compression: Compression,
+    blocks: &Arc<Mutex<Vec<Box<Block>>>>,
+    stop_loading: &Arc<AtomicBool>,
+    waker: &Waker,
+    report: &Arc<Mutex<Option<LoadReport>>>,
+) -> io::Result<BadLines> {
+    let file = ProgressReader::open(path, report)?;
+
+    let reader: Box<dyn Read + Send> = match compression {
+        Compression::Gzip => Box::new(MultiGzDecoder::new(file)),
+        Compression::Zstd => Box::new(ZstdDecoder::new(file)?),
+        Compression::Xz => Box::new(XzDecoder::new_multi_decoder(file)),
+    };
+
+    read_lines(reader, blocks, stop_loading, waker)
+}
+
+/// Reads lines from a pipe as they arrive, until the writer closes it or
+/// stop_loading is set.
+pub fn stream<R: Read + Send + 'static>(
+    reader: R,
+    blocks: &Arc<Mutex<Vec<Box<Block>>>>,
+    stop_loading: &Arc<AtomicBool>,
+    waker: &Waker,
+) -> io::Result<BadLines> {
+    read_lines(reader, blocks, stop_loading, waker)
+}
+
+/// Appends the lines of reader to blocks.
+fn read_lines<R: Read + Send + 'static>(
+    mut reader: R,
+    blocks: &Arc<Mutex<Vec<Box<Block>>>>,
+    stop_loading: &Arc<AtomicBool>,
+    waker: &Waker,
+) -> io::Result<BadLines> {
+    let (sender, receiver) = mpsc::channel();
+    let reader_stop = stop_loading.clone();
+
+    // Reads block until the writer sends something, so they get their own
+    // thread to keep loading stoppable
+    thread::spawn(move || {
+        let mut buffer = vec![0; 64 * 1024];
+
+        loop {
+            let result = match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => Ok(buffer[..read].to_vec()),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                // Serial ports time out while nothing arrives, which is when
+                // the port is let go after loading was stopped
+                Err(ref e) if e.kind() == io::ErrorKind::TimedOut => {
+                    if reader_stop.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    continue;
+                }
+                Err(e) => Err(e),
+            };
+            let failed = result.is_err();
+
+            if sender.send(result).is_err() || failed {
+                break;
+            }
+        }
+    });
+
+    let mut lines = Lines::new(0);
+    let mut values = Vec::new();
+    let mut unseen = false;
+
+    while !stop_loading.load(Ordering::SeqCst) {
+        match receiver.recv_timeout(Duration::from_millis(FOLLOW_INTERVAL)) {
+            Ok(bytes) => {
+                lines.feed(&bytes?, &mut values);
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                if unseen {
+                    waker.wake_now();
+                    unseen = false;
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                lines.finish(&mut values);
+                append(&mut blocks.lock().unwrap(), &values);
+                break;
+            }
+        }
+
+        if !values.is_empty() {
+            append(&mut blocks.lock().unwrap(), &values);
+            values.clear();
+            waker.wake();
+            unseen = true;
+        }
+    }
+
+    Ok(lines.bad_lines)
+}
+
+/// Whether path is a named pipe, which has to be streamed instead of loaded.
+#[cfg(unix)]
+pub fn is_fifo(path: &str) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+
+    fs::metadata(path).map_or(false, |metadata| metadata.file_type().is_fifo())
+}
+
+#[cfg(not(unix))]
+pub fn is_fifo(_path: &str) -> bool {
+    false
+}
+
+/// Splits bytes that arrive in pieces into lines and parses them.
+struct Lines {
+    line: Vec<u8>,
+
+    /// Number of the last finished line.
+    number: usize,
+    bad_lines: BadLines,
+}
+
+impl Lines {
+    fn new(number: usize) -> Lines {
+        Lines {
+            line: Vec::new(),
+            number: number,
+            bad_lines: BadLines::new(),
+        }
+    }
+
+    fn feed(&mut self, bytes: &[u8], values: &mut Vec<f64>) {
+        for &byte in bytes {
+            if byte == b'\n' {
+                self.finish(values);
+            } else {
+                self.line.push(byte);
+            }
+        }
+    }
+
+    /// Parses the line that is in progress.
+    fn finish(&mut self, values: &mut Vec<f64>) {
+        self.number += 1;
+
+        let line = trim(&self.line);
+        if !line.is_empty() {
+            match parse_value(line) {
+                Some(value) => values.push(value),
+                None => self.bad_lines.add(self.number, line),
+            }
+        }
+        self.line.clear();
+    }
+}
+
+/// Appends values, filling up the last block first so that only the last
+/// block is ever partial.
+pub fn append(blocks: &mut Vec<Box<Block>>, values: &[f64]) {
+    for &value in values {
+        let full = blocks.last().map_or(true, |block| block.data0.is_full());
+
+        if full {
+            blocks.push(Box::new(Block::new()));
+        }
+
+        if let Some(block) = blocks.last_mut() {
+            block.push(value);
+        }
+    }
+}
+
+/// The values of a chunk, with line numbers counted from the chunk start.
+struct Parsed {
+    values: Vec<f64>,
+    lines: usize,
+    bad_lines: BadLines,
+}
+
+/// Parses the lines that start in [start, end).
+fn parse_chunk(file: &mut File, start: u64, end: u64, stop_loading: &AtomicBool) -> io::Result<Parsed> {
+    // Start one byte early, so that a line starting exactly at start is not
+    // mistaken for the end of the previous chunk's last line
+    let mut position = start.saturating_sub(1);
+    file.seek(SeekFrom::Start(position))?;
+
+    let mut reader = BufReader::with_capacity(256 * 1024, file);
+    let mut line = Vec::new();
+    let mut values = Vec::with_capacity((end - start) as usize / 8);
+    let mut lines = 0;
+    let mut bad_lines = BadLines::new();
+
+    if start > 0 {
+        position += reader.read_until(b'\n', &mut line)? as u64;
+    }
+
+    while position < end {
+        line.clear();
+
+        let read = reader.read_until(b'\n', &mut line)?;
+        if read == 0 {
+            break;
+        }
+        position += read as u64;
+        lines += 1;
+
+        let trimmed = trim(&line);
+        if !trimmed.is_empty() {
+            match parse_value(trimmed) {
+                Some(value) => values.push(value),
+                None => bad_lines.add(lines, trimmed),
+            }
+        }
+
+        if lines % 4096 == 0 && stop_loading.load(Ordering::SeqCst) {
+            break;
+        }
+    }
+
+    Ok(Parsed {
+        values: values,
+        lines: lines,
+        bad_lines: bad_lines,
+    })
+}
+
+fn trim(bytes: &[u8]) -> &[u8] {
+    let start = bytes
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(bytes.len());
+    let end = bytes
+        .iter()
+        .rposition(|b| !b.is_ascii_whitespace())
+        .map_or(start, |i| i + 1);
+
+    &bytes[start..end]
+}
+
+/// Parses a value in any of the formats found in logs: decimals with a point
+/// or a comma, integers, and hexadecimal integers like `0x1f`.
+pub fn parse_value(bytes: &[u8]) -> Option<f64> {
+    if let Some(value) = parse_f64(bytes) {
+        return Some(value);
+    }
+
+    let (negative, digits) = match bytes.first() {
+        Some(&b'-') => (true, &bytes[1..]),
+        Some(&b'+') => (false, &bytes[1..]),
+        _ => (false, bytes),
+    };
+
+    if digits.starts_with(b"0x") || digits.starts_with(b"0X") {
+        let hex = ::std::str::from_utf8(&digits[2..]).ok()?;
+        let value = u64::from_str_radix(hex, 16).ok()? as f64;
+
+        return Some(if negative { -value } else { value });
+    }
+
+    // Decimal comma, as written in many locales
+    if bytes.iter().filter(|&&b| b == b',').count() == 1 && !bytes.contains(&b'.') {
+        let point = bytes
+            .iter()
+            .map(|&b| if b == b',' { b'.' } else { b })
+            .collect::<Vec<_>>();
+
+        return parse_f64(&point);
+    }
+
+    None
+}
+
+/// Parses plain decimals like `-12.375` without going through a string. Values
+/// that can't be parsed exactly this way are handed to the standard parser.
+pub fn parse_f64(bytes: &[u8]) -> Option<f64> {
+    let mut i = 0;
+    let negative = match bytes.first() {
+        Some(&b'-') => {
+            i += 1;
+            true
+        }
+        Some(&b'+') => {
+            i += 1;
+            false
+        }
+        _ => false,
+    };
+
+    let mut mantissa: u64 = 0;
+    let mut exponent: i32 = 0;
+    let mut digits = 0;
+
+    while i < bytes.len() && bytes[i].is_ascii_digit() {
+        mantissa = mantissa.wrapping_mul(10).wrapping_add((bytes[i] - b'0') as u64);
+        digits += 1;
+        i += 1;
+    }
+
+    if i < bytes.len() && bytes[i] == b'.' {
+        i += 1;
+
+        while i < bytes.len() && bytes[i].is_ascii_digit() {
+            mantissa = mantissa.wrapping_mul(10).wrapping_add((bytes[i] - b'0') as u64);
+            exponent -= 1;
+            digits += 1;
+            i += 1;
+        }
+    }
+
+    // Exact as long as the mantissa and the power of ten both fit in f64
+    if i == bytes.len() && digits > 0 && digits <= 15 && -exponent < POWERS_OF_TEN.len() as i32 {
+        let value = mantissa as f64 / POWERS_OF_TEN[-exponent as usize];
+
+        return Some(if negative { -value } else { value });
+    }
+
+    ::std::str::from_utf8(bytes).ok().and_then(|s| s.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Write;
+
+    #[test]
+    fn parses_decimal_commas() {
+        assert_eq!(parse_value(b"12,5"), Some(12.5));
+        assert_eq!(parse_value(b"-0,25"), Some(-0.25));
+        assert_eq!(parse_value(b"1,000.5"), None);
+        assert_eq!(parse_value(b"1,2,3"), None);
+    }
+
+    #[test]
+    fn parses_hex() {
+        assert_eq!(parse_value(b"-0x1f"), Some(-31.0));
+        assert_eq!(parse_value(b"0X10"), Some(16.0));
+        assert_eq!(parse_value(b"+0xff"), Some(255.0));
+        assert_eq!(parse_value(b"0xg"), None);
+    }
+
+    #[test]
+    fn parses_integers_and_blanks() {
+        assert_eq!(parse_value(b"42"), Some(42.0));
+        assert_eq!(parse_value(b"-7"), Some(-7.0));
+        assert_eq!(parse_value(b"+3"), Some(3.0));
+        assert_eq!(parse_value(b""), None);
+        assert_eq!(parse_value(b"-"), None);
+    }
+
+    #[test]
+    fn parses_f64() {
+        assert_eq!(parse_f64(b"-12.375"), Some(-12.375));
+        assert_eq!(parse_f64(b".5"), Some(0.5));
+        assert_eq!(parse_f64(b"0.1"), Some(0.1));
+        assert_eq!(parse_f64(b"1e3"), Some(1000.0));
+        assert_eq!(parse_f64(b"12345678901234567890"), Some(12345678901234567890.0));
+        assert_eq!(parse_f64(b"abc"), None);
+    }
+
+    #[test]
+    fn lines_across_pieces() {
+        let mut lines = Lines::new(0);
+        let mut values = Vec::new();
+
+        lines.feed(b"1\n0,5\nfo", &mut values);
+        lines.feed(b"o\n\n-0x2\n", &mut values);
+
+        assert_eq!(values, vec![1.0, 0.5, -2.0]);
+        assert_eq!(lines.bad_lines.count, 1);
+        assert_eq!(lines.bad_lines.samples, vec![(3, "foo".to_owned())]);
+    }
+
+    #[test]
+    fn chunk_line_numbers() {
+        let first = "1\n\nx\n2,5\n";
+        let second = "y\n0x10\n\nz\n";
+
+        let path = ::std::env::temp_dir().join(format!("plotter-rs-chunks-{}", ::std::process::id()));
+        fs::File::create(&path)
+            .and_then(|mut file| file.write_all(format!("{}{}", first, second).as_bytes()))
+            .unwrap();
+
+        let stop_loading = AtomicBool::new(false);
+        let mut file = File::open(&path).unwrap();
+        let end = (first.len() + second.len()) as u64;
+        let a = parse_chunk(&mut file, 0, first.len() as u64, &stop_loading).unwrap();
+        let b = parse_chunk(&mut file, first.len() as u64, end, &stop_loading).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(a.values, vec![1.0, 2.5]);
+        assert_eq!(b.values, vec![16.0]);
+        assert_eq!((a.lines, b.lines), (4, 4));
+
+        let mut bad_lines = BadLines::new();
+        bad_lines.merge(a.bad_lines, 0);
+        bad_lines.merge(b.bad_lines, a.lines);
+
+        assert_eq!(bad_lines.count, 3);
+        assert_eq!(
+            bad_lines.samples,
+            vec![(3, "x".to_owned()), (5, "y".to_owned()), (8, "z".to_owned())]
+        );
+    }
+}
@@ -12,6 +12,10 @@ impl Timer {
         }
     }
 
+    pub fn elapsed(&self) -> f64 {
+        time::precise_time_s() - self.start
+    }
+
     pub fn reset(&mut self) -> f64 {
         let now = time::precise_time_s();
         let delta = now - self.start;
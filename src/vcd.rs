@@ -0,0 +1,398 @@
+use std::cmp::max;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use {Block, LoadReport, ProgressReader, Waker};
+
+/// Timestamps looked at to find the sample interval.
+const GRID_TIMESTAMPS: usize = 10000;
+
+/// Most values the resampled variables may have together, about 2 GB.
+const MAX_VALUES: u64 = 256 * 1024 * 1024;
+
+/// Bytes at the end of the file searched for the last timestamp.
+const TAIL_BYTES: u64 = 1024 * 1024;
+
+/// A signal declared with `$var`.
+#[derive(Debug, Clone)]
+pub struct Var {
+    pub name: String,
+    pub width: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Header {
+    pub vars: Vec<Var>,
+
+    /// Seconds per time unit.
+    pub timescale: f64,
+
+    /// Variables by identifier code. Several variables can share a code.
+    ids: HashMap<String, Vec<usize>>,
+
+    /// Timestamp of the first sample and time units between samples. Value
+    /// changes are resampled onto this grid.
+    first_time: u64,
+    interval: u64,
+}
+
+impl Header {
+    /// Samples per second after resampling.
+    pub fn rate(&self) -> f64 {
+        1.0 / (self.interval as f64 * self.timescale)
+    }
+
+    /// Time of the first sample in seconds.
+    pub fn start(&self) -> f64 {
+        self.first_time as f64 * self.timescale
+    }
+}
+
+/// Reads the declarations up to `$enddefinitions` and finds the sample
+/// interval from the first timestamps. The interval is made coarser when the
+/// samples up to the last timestamp would not fit in MAX_VALUES.
+pub fn read_header(path: &str) -> io::Result<Header> {
+    let mut tokens = Tokens::new(BufReader::new(File::open(path)?));
+    let mut header = parse_header(&mut tokens)?;
+
+    let mut first = None;
+    let mut last = 0;
+    let mut interval = 0;
+    let mut seen = 0;
+
+    while let Some(token) = tokens.next()? {
+        if seen >= GRID_TIMESTAMPS {
+            break;
+        }
+
+        // Tokens are not empty, but may start with any character
+        let kind = match token.chars().next() {
+            Some(kind) => kind,
+            None => continue,
+        };
+        let rest = &token[kind.len_utf8()..];
+
+        match kind {
+            '#' => {
+                if let Ok(t) = rest.parse::<u64>() {
+                    match first {
+                        Some(first) => interval = gcd(interval, t.saturating_sub(first)),
+                        None => first = Some(t),
+                    }
+                    last = max(last, t);
+                    seen += 1;
+                }
+            }
+            // The identifier code after a vector may start with #
+            'b' | 'B' | 'r' | 'R' => {
+                tokens.next()?;
+            }
+            '$' if token == "$comment" => skip_command(&mut tokens)?,
+            _ => (),
+        }
+    }
+
+    let first = first.unwrap_or(0);
+    let last = max(last_timestamp(path)?.unwrap_or(last), first);
+    let interval = max(interval, 1);
+
+    // A few changes far apart on a fine grid need more samples than memory holds
+    let max_rows = max(MAX_VALUES / max(header.vars.len() as u64, 1), 1);
+    let rows = (last - first) / interval + 1;
+
+    header.first_time = first;
+    header.interval = interval * ((rows + max_rows - 1) / max_rows);
+
+    Ok(header)
+}
+
+/// The last timestamp in the end of the file, if there is one.
+fn last_timestamp(path: &str) -> io::Result<Option<u64>> {
+    let mut file = File::open(path)?;
+    let start = file.metadata()?.len().saturating_sub(TAIL_BYTES);
+    let mut tail = Vec::new();
+
+    file.seek(SeekFrom::Start(start))?;
+    file.read_to_end(&mut tail)?;
+
+    let tail = String::from_utf8_lossy(&tail);
+    let tokens = tail.split_whitespace().collect::<Vec<_>>();
+
+    // The first token may be cut off
+    let first = if start > 0 { 1 } else { 0 };
+
+    for i in (first..tokens.len()).rev() {
+        let timestamp = tokens[i].starts_with('#')
+            && !(i > 0 && tokens[i - 1].starts_with(|c| "bBrR".contains(c)));
+
+        if timestamp {
+            if let Ok(t) = tokens[i][1..].parse() {
+                return Ok(Some(t));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Loads the value changes, resampled onto the grid of the header. Each
+/// sample holds the values at its time, so changes that are shorter than the
+/// interval can be missed. Unknown and high impedance values leave a gap.
+/// Returns how many timestamps fall between samples, whose changes show at
+/// the next sample.
+pub fn load(
+    path: &str,
+    header: &Header,
+    channels: &[Arc<Mutex<Vec<Box<Block>>>>],
+    stop_loading: &AtomicBool,
+    waker: &Waker,
+    report: &Arc<Mutex<Option<LoadReport>>>,
+) -> io::Result<u64> {
+    let mut tokens = Tokens::new(BufReader::new(ProgressReader::open(path, report)?));
+    parse_header(&mut tokens)?;
+
+    let mut values = vec![::std::f64::NAN; header.vars.len()];
+    let mut current_time = None;
+    let mut next_sample = header.first_time;
+    let mut off_grid = 0;
+
+    let mut blocks = channels.iter().map(|_| Box::new(Block::new())).collect::<Vec<_>>();
+
+    let push_row = |values: &[f64], blocks: &mut Vec<Box<Block>>| {
+        if blocks.iter().any(|block| block.data0.is_full()) {
+            for (block, target) in blocks.iter_mut().zip(channels.iter()) {
+                let full = ::std::mem::replace(block, Box::new(Block::new()));
+                target.lock().unwrap().push(full);
+            }
+
+            waker.wake();
+        }
+
+        for (block, &value) in blocks.iter_mut().zip(values.iter()) {
+            block.push(value);
+        }
+    };
+
+    while let Some(token) = tokens.next()? {
+        if stop_loading.load(Ordering::SeqCst) {
+            break;
+        }
+
+        // Tokens are not empty, but may start with any character
+        let kind = match token.chars().next() {
+            Some(kind) => kind,
+            None => continue,
+        };
+        let rest = &token[kind.len_utf8()..];
+
+        match kind {
+            '#' => {
+                if let Ok(t) = rest.parse::<u64>() {
+                    // The values hold on every sample up to the new timestamp
+                    if current_time.is_some() {
+                        while next_sample < t && !stop_loading.load(Ordering::SeqCst) {
+                            push_row(&values, &mut blocks);
+                            next_sample += header.interval;
+                        }
+                    }
+
+                    if t < header.first_time || (t - header.first_time) % header.interval != 0 {
+                        off_grid += 1;
+                    }
+
+                    current_time = Some(t);
+                }
+            }
+            '0' | '1' | 'x' | 'X' | 'z' | 'Z' => {
+                set(header, &mut values, rest, scalar_value(kind));
+            }
+            'b' | 'B' => {
+                if let Some(id) = tokens.next()? {
+                    set(header, &mut values, &id, vector_value(rest));
+                }
+            }
+            'r' | 'R' => {
+                if let Some(id) = tokens.next()? {
+                    set(header, &mut values, &id, rest.parse().unwrap_or(::std::f64::NAN));
+                }
+            }
+            '$' if token == "$comment" => skip_command(&mut tokens)?,
+            _ => (),
+        }
+    }
+
+    if let Some(t) = current_time {
+        while next_sample <= t && !stop_loading.load(Ordering::SeqCst) {
+            push_row(&values, &mut blocks);
+            next_sample += header.interval;
+        }
+    }
+
+    for (block, target) in blocks.into_iter().zip(channels.iter()) {
+        if block.data0.len() != 0 {
+            target.lock().unwrap().push(block);
+        }
+    }
+
+    Ok(off_grid)
+}
+
+fn set(header: &Header, values: &mut [f64], id: &str, value: f64) {
+    if let Some(vars) = header.ids.get(id) {
+        for &var in vars {
+            values[var] = value;
+        }
+    }
+}
+
+fn scalar_value(value: char) -> f64 {
+    match value {
+        '0' => 0.0,
+        '1' => 1.0,
+        _ => ::std::f64::NAN,
+    }
+}
+
+/// Binary vectors as unsigned integers.
+fn vector_value(bits: &str) -> f64 {
+    bits.chars().fold(0.0, |value, bit| match bit {
+        '0' => value * 2.0,
+        '1' => value * 2.0 + 1.0,
+        _ => ::std::f64::NAN,
+    })
+}
+
+fn parse_header<R: BufRead>(tokens: &mut Tokens<R>) -> io::Result<Header> {
+    let mut header = Header {
+        vars: Vec::new(),
+        timescale: 1.0,
+        ids: HashMap::new(),
+        first_time: 0,
+        interval: 1,
+    };
+    let mut scopes = Vec::new();
+
+    while let Some(token) = tokens.next()? {
+        match token.as_str() {
+            "$timescale" => {
+                let timescale = read_command(tokens)?.join("");
+                header.timescale = parse_timescale(&timescale).unwrap_or(1.0);
+            }
+            "$scope" => {
+                let parts = read_command(tokens)?;
+                scopes.push(parts.get(1).cloned().unwrap_or_default());
+            }
+            "$upscope" => {
+                skip_command(tokens)?;
+                scopes.pop();
+            }
+            "$var" => {
+                // $var type width id name [range] $end
+                let parts = read_command(tokens)?;
+
+                if parts.len() >= 4 {
+                    let mut path = scopes.clone();
+                    path.push(parts[3..].join(""));
+
+                    header
+                        .ids
+                        .entry(parts[2].clone())
+                        .or_insert_with(Vec::new)
+                        .push(header.vars.len());
+                    header.vars.push(Var {
+                        name: path.join("."),
+                        width: parts[1].parse().unwrap_or(1),
+                    });
+                }
+            }
+            "$enddefinitions" => {
+                skip_command(tokens)?;
+                return Ok(header);
+            }
+            _ if token.starts_with('$') => skip_command(tokens)?,
+            _ => (),
+        }
+    }
+
+    Err(io::Error::new(io::ErrorKind::InvalidData, "No $enddefinitions in VCD file"))
+}
+
+/// Seconds per unit of something like `1ns` or `100 us`.
+fn parse_timescale(timescale: &str) -> Option<f64> {
+    let split = timescale
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(timescale.len());
+    let (number, unit) = timescale.split_at(split);
+
+    let unit = match unit.trim() {
+        "s" => 1.0,
+        "ms" => 1e-3,
+        "us" => 1e-6,
+        "ns" => 1e-9,
+        "ps" => 1e-12,
+        "fs" => 1e-15,
+        _ => return None,
+    };
+
+    number.parse::<f64>().ok().map(|number| number * unit)
+}
+
+/// The tokens up to the next `$end`.
+fn read_command<R: BufRead>(tokens: &mut Tokens<R>) -> io::Result<Vec<String>> {
+    let mut parts = Vec::new();
+
+    while let Some(token) = tokens.next()? {
+        if token == "$end" {
+            break;
+        }
+        parts.push(token);
+    }
+
+    Ok(parts)
+}
+
+fn skip_command<R: BufRead>(tokens: &mut Tokens<R>) -> io::Result<()> {
+    read_command(tokens).map(|_| ())
+}
+
+/// Whitespace separated words, which is all the structure VCD has.
+struct Tokens<R> {
+    reader: R,
+    line: String,
+    words: Vec<String>,
+}
+
+impl<R: BufRead> Tokens<R> {
+    fn new(reader: R) -> Tokens<R> {
+        Tokens {
+            reader: reader,
+            line: String::new(),
+            words: Vec::new(),
+        }
+    }
+
+    fn next(&mut self) -> io::Result<Option<String>> {
+        while self.words.is_empty() {
+            self.line.clear();
+
+            if self.reader.read_line(&mut self.line)? == 0 {
+                return Ok(None);
+            }
+
+            self.words = self.line.split_whitespace().rev().map(|word| word.to_owned()).collect();
+        }
+
+        Ok(self.words.pop())
+    }
+}
@@ -0,0 +1,154 @@
+use hound::{self, SampleFormat, WavReader, WavSpec, WavWriter};
+use imgui::{ImStr, Ui};
+use std::cmp::max;
+use std::io::BufReader;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use export;
+use {Block, LoadReport, ProgressReader, Waker};
+
+/// Whether the file starts with a RIFF WAVE header.
+pub fn is_wav(path: &str) -> bool {
+    WavReader::open(path).is_ok()
+}
+
+pub fn spec(path: &str) -> hound::Result<WavSpec> {
+    WavReader::open(path).map(|reader| reader.spec())
+}
+
+/// Reads every WAV channel into its own block list. Integer samples are scaled
+/// to [-1, 1].
+pub fn load(
+    path: &str,
+    channels: &[Arc<Mutex<Vec<Box<Block>>>>],
+    stop_loading: &AtomicBool,
+    waker: &Waker,
+    report: &Arc<Mutex<Option<LoadReport>>>,
+) -> hound::Result<()> {
+    let mut reader = WavReader::new(BufReader::new(ProgressReader::open(path, report)?))?;
+    let spec = reader.spec();
+
+    match spec.sample_format {
+        SampleFormat::Float => {
+            let samples = reader.samples::<f32>().map(|sample| sample.map(|value| value as f64));
+            push_samples(samples, channels, stop_loading, waker)
+        }
+        SampleFormat::Int => {
+            let full_scale = (1u64 << (spec.bits_per_sample - 1)) as f64;
+            let samples = reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|value| value as f64 / full_scale));
+            push_samples(samples, channels, stop_loading, waker)
+        }
+    }
+}
+
+/// Distributes interleaved samples over the channels.
+fn push_samples<I: Iterator<Item = hound::Result<f64>>>(
+    samples: I,
+    channels: &[Arc<Mutex<Vec<Box<Block>>>>],
+    stop_loading: &AtomicBool,
+    waker: &Waker,
+) -> hound::Result<()> {
+    let mut blocks = channels.iter().map(|_| Box::new(Block::new())).collect::<Vec<_>>();
+
+    for (i, sample) in samples.enumerate() {
+        let channel = i % channels.len();
+
+        if channel == 0 {
+            if stop_loading.load(Ordering::SeqCst) {
+                break;
+            }
+
+            if blocks[0].data0.is_full() {
+                for (block, target) in blocks.iter_mut().zip(channels.iter()) {
+                    let full = ::std::mem::replace(block, Box::new(Block::new()));
+                    target.lock().unwrap().push(full);
+                }
+
+                waker.wake();
+            }
+        }
+
+        blocks[channel].push(sample?);
+    }
+
+    for (block, target) in blocks.into_iter().zip(channels.iter()) {
+        if block.data0.len() != 0 {
+            target.lock().unwrap().push(block);
+        }
+    }
+
+    Ok(())
+}
+
+/// Options of a WAV export.
+#[derive(Debug)]
+pub struct Options {
+    pub sample_rate: i32,
+
+    /// 32 bit float samples instead of 16 bit integers.
+    pub float: bool,
+}
+
+impl Options {
+    /// Defaults to the sample rate of the channels, if they have one.
+    pub fn new(sample_rate: Option<f64>) -> Options {
+        Options {
+            sample_rate: sample_rate.map_or(48000, |rate| rate.round() as i32),
+            float: true,
+        }
+    }
+}
+
+impl export::Format for Options {
+    type Error = hound::Error;
+
+    fn title(&self) -> &'static ImStr {
+        im_str!("Export WAV")
+    }
+
+    fn extension(&self) -> &'static str {
+        "wav"
+    }
+
+    fn options(&mut self, ui: &Ui) {
+        ui.input_int(im_str!("Sample rate"), &mut self.sample_rate).build();
+        ui.checkbox(im_str!("32 bit float"), &mut self.float);
+
+        self.sample_rate = max(self.sample_rate, 1);
+    }
+
+    /// Writes the channels interleaved. Channels that end early are padded
+    /// with silence, and 16 bit samples are clipped to [-1, 1].
+    fn write(&self, path: &str, selected: &[Vec<f64>], rows: usize) -> hound::Result<()> {
+        let spec = WavSpec {
+            channels: selected.len() as u16,
+            sample_rate: self.sample_rate as u32,
+            bits_per_sample: if self.float { 32 } else { 16 },
+            sample_format: if self.float {
+                SampleFormat::Float
+            } else {
+                SampleFormat::Int
+            },
+        };
+
+        let mut writer = WavWriter::create(path, spec)?;
+
+        for i in 0..rows {
+            for samples in selected {
+                let value = samples.get(i).cloned().unwrap_or(0.0);
+                let value = if value.is_nan() { 0.0 } else { value };
+
+                if self.float {
+                    writer.write_sample(value as f32)?;
+                } else {
+                    writer.write_sample((value.max(-1.0).min(1.0) * 32767.0) as i16)?;
+                }
+            }
+        }
+
+        writer.finalize()
+    }
+}